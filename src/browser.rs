@@ -0,0 +1,204 @@
+//! Support for the `$BROWSER` convention described at <http://www.catb.org/~esr/BROWSER/>.
+//!
+//! The variable holds a list of candidate commands, separated by `:` (`;` on Windows). Each
+//! candidate may contain `%s`, which is replaced with the target, and `%%`, which is replaced with
+//! a single `%`. If a candidate has no `%s`, the target is appended as the last argument.
+
+use crate::{CommandSpec, Error, PathOrURI, Platform};
+
+#[cfg(not(target_os = "windows"))]
+const SEPARATOR: char = ':';
+#[cfg(target_os = "windows")]
+const SEPARATOR: char = ';';
+
+/// The parsed value of a variable following the `$BROWSER` convention.
+#[derive(Debug, PartialEq, Eq)]
+enum Browsers {
+    /// The variable was set to an empty string or `none`.
+    Disabled,
+    /// The candidate command lines, in order of preference.
+    Candidates(Vec<Vec<String>>),
+}
+
+fn parse(var: &str, value: &str) -> crate::Result<Browsers> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("none") {
+        return Ok(Browsers::Disabled);
+    }
+
+    trimmed
+        .split(SEPARATOR)
+        .map(|candidate| {
+            shell_words::split(candidate).map_err(|error| Error::ParseCommand {
+                var: var.to_string(),
+                value: value.to_string(),
+                error,
+            })
+        })
+        .filter(|words| !matches!(words, Ok(words) if words.is_empty()))
+        .collect::<crate::Result<_>>()
+        .map(Browsers::Candidates)
+}
+
+/// Replaces `%s` with `target` and `%%` with `%` in `word`.
+///
+/// Returns whether `%s` was found.
+fn substitute(word: &str, target: &str) -> (String, bool) {
+    let mut result = String::with_capacity(word.len());
+    let mut substituted = false;
+    let mut chars = word.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => {
+                result.push_str(target);
+                substituted = true;
+            }
+            Some('%') | None => result.push('%'),
+            Some(other) => {
+                result.push('%');
+                result.push(other);
+            }
+        }
+    }
+
+    (result, substituted)
+}
+
+/// Builds the full command line for a single candidate.
+fn command_line(words: &[String], target: &str) -> Vec<String> {
+    let mut substituted = false;
+    let mut line: Vec<String> = words
+        .iter()
+        .map(|word| {
+            let (word, found) = substitute(word, target);
+            substituted |= found;
+            word
+        })
+        .collect();
+
+    if !substituted {
+        line.push(target.to_string());
+    }
+
+    line
+}
+
+/// Generate a command for the first usable candidate in `value`, read from the environment
+/// variable `var`.
+///
+/// # Errors
+///
+/// - [`Error::BrowserDisabled`] if the value is empty or `none`.
+/// - [`Error::NoOpener`] if no candidate's program exists.
+pub(crate) fn command(
+    platform: Platform,
+    var: &str,
+    value: &str,
+    target: &PathOrURI,
+) -> crate::Result<CommandSpec> {
+    let candidates = match parse(var, value)? {
+        Browsers::Disabled => return Err(Error::BrowserDisabled),
+        Browsers::Candidates(candidates) => candidates,
+    };
+
+    let target_str = target.to_arg()?;
+    for candidate in &candidates {
+        let line = command_line(candidate, &target_str);
+        let Some((cmd, args)) = line.split_first() else {
+            continue;
+        };
+        match crate::ensure_command_on(platform, cmd) {
+            Ok(()) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("opening {} with {:?}", target_str, line);
+
+                return Ok(CommandSpec::new(cmd.clone()).args(args.to_vec()));
+            }
+            #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("skipping {} candidate: {}", var, err);
+            }
+        }
+    }
+
+    Err(Error::NoOpener {
+        tried: candidates
+            .into_iter()
+            .filter_map(|candidate| candidate.into_iter().next())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BROWSER_ENV;
+
+    fn words(line: &[&str]) -> Vec<String> {
+        line.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_disabled() {
+        assert_eq!(parse(BROWSER_ENV, "").unwrap(), Browsers::Disabled);
+        assert_eq!(parse(BROWSER_ENV, "  ").unwrap(), Browsers::Disabled);
+        assert_eq!(parse(BROWSER_ENV, "none").unwrap(), Browsers::Disabled);
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_parse_candidates() {
+        assert_eq!(
+            parse(
+                BROWSER_ENV,
+                "firefox --new-window %s::w3m:'/opt/my browser/run'"
+            )
+            .unwrap(),
+            Browsers::Candidates(vec![
+                words(&["firefox", "--new-window", "%s"]),
+                words(&["w3m"]),
+                words(&["/opt/my browser/run"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert!(matches!(
+            parse(BROWSER_ENV, "firefox 'oops"),
+            Err(Error::ParseCommand { .. })
+        ));
+    }
+
+    #[test]
+    fn test_substitute() {
+        assert_eq!(
+            substitute("%s", "https://example.com"),
+            ("https://example.com".into(), true)
+        );
+        assert_eq!(
+            substitute("--url=%s", "https://example.com"),
+            ("--url=https://example.com".into(), true)
+        );
+        assert_eq!(substitute("100%%", "x"), ("100%".into(), false));
+        assert_eq!(substitute("%%s", "x"), ("%s".into(), false));
+        assert_eq!(substitute("%d%", "x"), ("%d%".into(), false));
+    }
+
+    #[test]
+    fn test_command_line() {
+        let url = "https://example.com/?q=%20";
+        assert_eq!(
+            command_line(&words(&["firefox", "-new-tab", "%s"]), url),
+            words(&["firefox", "-new-tab", url])
+        );
+        assert_eq!(command_line(&words(&["lynx"]), url), words(&["lynx", url]));
+    }
+}
@@ -0,0 +1,205 @@
+use std::{fmt::Display, path::PathBuf, process::Command};
+
+/// A description of a command to run, which can be inspected, compared, and converted into a
+/// [`Command`].
+///
+/// Unlike [`Command`], this implements [`PartialEq`] and a useful [`Debug`], and can be
+/// (de)serialized with the `serde` feature. Its [`Display`] implementation shows the program and
+/// arguments quoted for a POSIX shell, which is useful for dry runs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommandSpec {
+    /// The program to run.
+    pub program: String,
+    /// The arguments to pass to the program.
+    pub args: Vec<String>,
+    /// Changes to the environment, in order. A value of `None` removes the variable.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub env: Vec<(String, Option<String>)>,
+    /// The working directory to run the program in, if not the current one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub current_dir: Option<PathBuf>,
+    /// Whether the arguments are already escaped for the program's own command-line parser and
+    /// must be passed verbatim instead of quoted.
+    ///
+    /// This only makes a difference on Windows, where each program parses its own command line.
+    /// It is used for commands run through `cmd`, which does not follow the usual quoting rules.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub verbatim_args: bool,
+}
+
+impl CommandSpec {
+    /// Create a new spec that runs `program` with no arguments.
+    #[must_use]
+    pub fn new<S: Into<String>>(program: S) -> Self {
+        Self {
+            program: program.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Add an argument to pass to the program.
+    #[must_use]
+    pub fn arg<S: Into<String>>(mut self, arg: S) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Add multiple arguments to pass to the program.
+    #[must_use]
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set an environment variable for the program.
+    #[must_use]
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.env.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Remove an environment variable for the program.
+    #[must_use]
+    pub fn env_remove<K: Into<String>>(mut self, key: K) -> Self {
+        self.env.push((key.into(), None));
+        self
+    }
+
+    /// Set the working directory for the program.
+    #[must_use]
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Set whether the arguments are passed verbatim. See [`CommandSpec::verbatim_args`].
+    #[must_use]
+    pub fn verbatim_args(mut self, verbatim: bool) -> Self {
+        self.verbatim_args = verbatim;
+        self
+    }
+}
+
+impl CommandSpec {
+    /// Returns a copy of this spec without its last argument.
+    pub(crate) fn without_last_arg(&self) -> Self {
+        let mut spec = self.clone();
+        spec.args.pop();
+        spec
+    }
+}
+
+impl Display for CommandSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let words = std::iter::once(&self.program).chain(&self.args);
+        if self.verbatim_args {
+            let words: Vec<&str> = words.map(String::as_str).collect();
+            write!(f, "{}", words.join(" "))
+        } else {
+            write!(f, "{}", shell_words::join(words))
+        }
+    }
+}
+
+impl From<&CommandSpec> for Command {
+    fn from(spec: &CommandSpec) -> Self {
+        let mut cmd = Command::new(&spec.program);
+        if spec.verbatim_args {
+            add_verbatim_args(&mut cmd, &spec.args);
+        } else {
+            cmd.args(&spec.args);
+        }
+        for (key, value) in &spec.env {
+            match value {
+                Some(value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        }
+        if let Some(dir) = &spec.current_dir {
+            cmd.current_dir(dir);
+        }
+        cmd
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn add_verbatim_args(cmd: &mut Command, args: &[String]) {
+    use std::os::windows::process::CommandExt;
+
+    for arg in args {
+        cmd.raw_arg(arg);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn add_verbatim_args(cmd: &mut Command, args: &[String]) {
+    cmd.args(args);
+}
+
+impl From<CommandSpec> for Command {
+    fn from(spec: CommandSpec) -> Self {
+        Self::from(&spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let spec = CommandSpec::new("xdg-open").arg("/tmp/my file.txt");
+        assert_eq!(spec.to_string(), "xdg-open '/tmp/my file.txt'");
+    }
+
+    #[test]
+    fn test_display_verbatim() {
+        let spec = CommandSpec::new("cmd")
+            .args(["/c", "start", "\"\"", "https://x/?a=1^&b=2"])
+            .verbatim_args(true);
+        assert_eq!(spec.to_string(), r#"cmd /c start "" https://x/?a=1^&b=2"#);
+    }
+
+    #[test]
+    fn test_into_command() {
+        let spec = CommandSpec::new("code")
+            .args(["--wait", "notes.md"])
+            .env("LANG", "C")
+            .env_remove("DISPLAY")
+            .current_dir("/tmp");
+        let cmd = Command::from(&spec);
+
+        assert_eq!(cmd.get_program(), "code");
+        assert_eq!(
+            cmd.get_args().collect::<Vec<_>>(),
+            vec![OsStr::new("--wait"), OsStr::new("notes.md")]
+        );
+        assert_eq!(
+            cmd.get_envs().collect::<Vec<_>>(),
+            vec![
+                (OsStr::new("DISPLAY"), None),
+                (OsStr::new("LANG"), Some(OsStr::new("C")))
+            ]
+        );
+        assert_eq!(cmd.get_current_dir(), Some("/tmp".as_ref()));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let spec = CommandSpec::new("open").arg("https://example.com");
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(
+            json,
+            r#"{"program":"open","args":["https://example.com"],"env":[],"current_dir":null,"verbatim_args":false}"#
+        );
+        assert_eq!(serde_json::from_str::<CommandSpec>(&json).unwrap(), spec);
+    }
+}
@@ -0,0 +1,342 @@
+//! Parsing of [desktop entry files], which describe how applications on Linux are launched.
+//!
+//! [desktop entry files]: https://specifications.freedesktop.org/desktop-entry-spec/latest/
+
+use std::path::{Path, PathBuf};
+
+use crate::Environment;
+
+const DESKTOP_ENTRY_GROUP: &str = "[Desktop Entry]";
+
+/// The parts of a desktop entry that matter for launching it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct DesktopEntry {
+    /// Where the entry was loaded from.
+    pub(crate) path: PathBuf,
+    pub(crate) name: Option<String>,
+    pub(crate) icon: Option<String>,
+    pub(crate) exec: Option<String>,
+    pub(crate) dbus_activatable: bool,
+    /// Whether the program must run in a terminal.
+    pub(crate) terminal: bool,
+}
+
+/// Replaces the escape sequences allowed in string values.
+fn unescape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Splits an `Exec` value into arguments, following its quoting rules.
+///
+/// Returns `None` if a quote is left unterminated.
+fn split_exec(exec: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            c @ ('"' | '`' | '$' | '\\') => word.push(c),
+                            other => {
+                                word.push('\\');
+                                word.push(other);
+                            }
+                        },
+                        other => word.push(other),
+                    }
+                }
+            }
+            other => word.get_or_insert_with(String::new).push(other),
+        }
+    }
+
+    words.extend(word);
+    Some(words)
+}
+
+impl DesktopEntry {
+    /// Parses the `[Desktop Entry]` group of a desktop file. Localized keys are ignored.
+    pub(crate) fn parse(path: &Path, contents: &str) -> Self {
+        let mut entry = Self {
+            path: path.to_path_buf(),
+            ..Self::default()
+        };
+        let mut in_group = false;
+
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_group = line == DESKTOP_ENTRY_GROUP;
+                continue;
+            }
+            if !in_group || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = unescape_value(value.trim());
+            match key.trim() {
+                "Name" => entry.name = Some(value),
+                "Icon" => entry.icon = Some(value),
+                "Exec" => entry.exec = Some(value),
+                "DBusActivatable" => entry.dbus_activatable = value == "true",
+                "Terminal" => entry.terminal = value == "true",
+                _ => {}
+            }
+        }
+
+        entry
+    }
+
+    /// Loads the desktop file at `path`, returning `None` if it cannot be read.
+    pub(crate) fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        Some(Self::parse(path, &contents))
+    }
+
+    /// Finds the desktop entry with the given desktop file ID in the `applications` subdirectory
+    /// of the given data directories, in order.
+    ///
+    /// A `-` in the ID may also stand for a subdirectory, so `kde-okular.desktop` is also looked
+    /// up as `kde/okular.desktop`.
+    pub(crate) fn find(id: &str, data_dirs: &[PathBuf]) -> Option<Self> {
+        let id = if id.ends_with(".desktop") {
+            id.to_string()
+        } else {
+            format!("{id}.desktop")
+        };
+
+        let mut candidates = vec![PathBuf::from(&id)];
+        candidates.extend(
+            id.match_indices('-')
+                .map(|(i, _)| Path::new(&id[..i]).join(&id[i + 1..])),
+        );
+
+        data_dirs.iter().find_map(|dir| {
+            candidates
+                .iter()
+                .find_map(|candidate| Self::load(&dir.join("applications").join(candidate)))
+        })
+    }
+
+    fn exec_words(&self) -> Option<Vec<String>> {
+        split_exec(self.exec.as_deref()?)
+    }
+
+    /// Returns the name of the program the `Exec` key runs.
+    pub(crate) fn program(&self) -> Option<String> {
+        self.exec_words()?.into_iter().next()
+    }
+
+    /// Returns whether the `Exec` key accepts several targets at once, with `%F` or `%U`.
+    pub(crate) fn accepts_multiple(&self) -> bool {
+        self.exec_words()
+            .is_some_and(|words| words.iter().any(|word| word == "%F" || word == "%U"))
+    }
+
+    /// Expands the `Exec` key into a full command line for the given targets, which should already
+    /// be converted to arguments.
+    ///
+    /// Returns `None` if there is no `Exec` key, it is malformed, or it expands to nothing.
+    pub(crate) fn command_line(&self, targets: &[String]) -> Option<Vec<String>> {
+        let mut line = Vec::new();
+
+        for word in self.exec_words()? {
+            match word.as_str() {
+                "%f" | "%u" => line.extend(targets.first().cloned()),
+                "%F" | "%U" => line.extend(targets.iter().cloned()),
+                "%i" => {
+                    if let Some(icon) = &self.icon {
+                        line.push("--icon".to_string());
+                        line.push(icon.clone());
+                    }
+                }
+                _ => line.push(self.expand_inline(&word, targets.first())),
+            }
+        }
+
+        line.retain(|word| !word.is_empty());
+        Some(line).filter(|line| !line.is_empty())
+    }
+
+    /// Expands field codes that appear inside a larger argument.
+    fn expand_inline(&self, word: &str, target: Option<&String>) -> String {
+        let mut result = String::with_capacity(word.len());
+        let mut chars = word.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => result.push('%'),
+                Some('f' | 'u' | 'F' | 'U') => result.push_str(target.map_or("", String::as_str)),
+                Some('c') => result.push_str(self.name.as_deref().unwrap_or_default()),
+                Some('k') => result.push_str(&self.path.to_string_lossy()),
+                // Deprecated and unknown field codes are removed.
+                _ => {}
+            }
+        }
+        result
+    }
+}
+
+/// Returns the XDG data directories in `env`, in order of preference.
+pub(crate) fn data_dirs<E: Environment + ?Sized>(env: &E) -> Vec<PathBuf> {
+    let home = env
+        .var("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env.var("HOME")
+                .map(|home| Path::new(&home).join(".local/share"))
+        });
+    let dirs = env
+        .var("XDG_DATA_DIRS")
+        .and_then(|dirs| dirs.into_string().ok())
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    home.into_iter()
+        .chain(dirs.split(':').filter(|s| !s.is_empty()).map(PathBuf::from))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OKULAR: &str = r#"[Desktop Entry]
+Name=Okular
+Name[de]=Okular-Dokumentenbetrachter
+Icon=okular
+Exec=okular --name "Doc \\"Viewer\\"" %U %i
+MimeType=application/pdf;image/png;
+Terminal=false
+
+[Desktop Action new]
+Exec=okular --new
+"#;
+
+    fn entry(exec: &str) -> DesktopEntry {
+        DesktopEntry::parse(
+            Path::new("/apps/test.desktop"),
+            &format!("[Desktop Entry]\nName=Test\nExec={exec}\n"),
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let entry = DesktopEntry::parse(Path::new("/apps/okular.desktop"), OKULAR);
+        assert_eq!(entry.name.as_deref(), Some("Okular"));
+        assert_eq!(
+            entry.exec.as_deref(),
+            Some(r#"okular --name "Doc \"Viewer\"" %U %i"#)
+        );
+        assert_eq!(entry.icon.as_deref(), Some("okular"));
+        assert!(!entry.dbus_activatable);
+        assert!(!entry.terminal);
+        assert!(self::entry("vim %F\nTerminal=true").terminal);
+    }
+
+    #[test]
+    fn test_command_line() {
+        let entry = DesktopEntry::parse(Path::new("/apps/okular.desktop"), OKULAR);
+        let targets = ["/tmp/a.pdf".to_string(), "/tmp/b c.pdf".to_string()];
+        assert_eq!(
+            entry.command_line(&targets).unwrap(),
+            vec![
+                "okular",
+                "--name",
+                "Doc \"Viewer\"",
+                "/tmp/a.pdf",
+                "/tmp/b c.pdf",
+                "--icon",
+                "okular"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_line_field_codes() {
+        let target = "/tmp/a.txt".to_string();
+        assert_eq!(
+            entry("edit --file=%f --title=%c %k 100%% %d")
+                .command_line(std::slice::from_ref(&target))
+                .unwrap(),
+            vec![
+                "edit",
+                "--file=/tmp/a.txt",
+                "--title=Test",
+                "/apps/test.desktop",
+                "100%"
+            ]
+        );
+        assert_eq!(
+            entry("single %f")
+                .command_line(&[target.clone(), target.clone()])
+                .unwrap(),
+            vec!["single", "/tmp/a.txt"]
+        );
+        assert_eq!(
+            entry("noargs")
+                .command_line(std::slice::from_ref(&target))
+                .unwrap(),
+            vec!["noargs"]
+        );
+        assert_eq!(
+            entry(r#"broken "quote"#).command_line(std::slice::from_ref(&target)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path().join("applications");
+        std::fs::create_dir_all(apps.join("kde")).unwrap();
+        std::fs::write(apps.join("kde/okular.desktop"), OKULAR).unwrap();
+        std::fs::write(apps.join("other.desktop"), "[Desktop Entry]\nExec=other\n").unwrap();
+
+        let dirs = vec![PathBuf::from("/nonexistent"), dir.path().to_path_buf()];
+        assert_eq!(
+            DesktopEntry::find("kde-okular.desktop", &dirs)
+                .unwrap()
+                .name
+                .as_deref(),
+            Some("Okular")
+        );
+        assert_eq!(
+            DesktopEntry::find("other", &dirs).unwrap().exec.as_deref(),
+            Some("other")
+        );
+        assert_eq!(DesktopEntry::find("missing.desktop", &dirs), None);
+    }
+}
@@ -0,0 +1,546 @@
+//! Editing text in the user's editor, like `git commit` does.
+
+use std::{
+    io::Write,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use crate::{EditorChain, Environment, Error, PathOrURI, Platform, Result, SystemEnvironment};
+
+/// How soon a terminal emulator must exit without the file being changed to be considered as
+/// having handed the editor off instead of running it. Nobody opens and quits an editor faster.
+const DETACHED_TERMINAL: Duration = Duration::from_millis(500);
+
+/// Options for [`edit_text`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditOptions {
+    extension: Option<String>,
+    comment_prefix: Option<String>,
+    chain: EditorChain,
+}
+
+impl EditOptions {
+    /// Create the default options, which use the default [`EditorChain`], no extension, and keep
+    /// all lines.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The extension of the temporary file, without the leading `.`, so that the editor can
+    /// highlight its syntax, e.g. `md` or `toml`.
+    #[must_use]
+    pub fn extension<S: Into<String>>(mut self, extension: S) -> Self {
+        self.extension = Some(extension.into());
+        self
+    }
+
+    /// Remove lines that start with `prefix`, such as `#`, from the edited text. Like with
+    /// `git commit`, these can be used to show instructions in the initial text.
+    #[must_use]
+    pub fn strip_comments<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.comment_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Where to look for the editor. Defaults to [`EditorChain::default`].
+    #[must_use]
+    pub fn editor_chain(mut self, chain: EditorChain) -> Self {
+        self.chain = chain;
+        self
+    }
+
+    /// Removes comment lines from `text`, if configured.
+    fn strip(&self, text: &str) -> String {
+        match &self.comment_prefix {
+            Some(prefix) => text
+                .split_inclusive('\n')
+                .filter(|line| !line.starts_with(prefix.as_str()))
+                .collect(),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Lets the user edit `initial` in their text editor and returns the result, or `None` if they
+/// aborted.
+///
+/// The text is written to a temporary file that only the current user can access, which is
+/// opened with the editor found by the [`EditorChain`] from `options`. GUI editors that would
+/// return immediately, such as `code` or `subl`, are passed a flag like `--wait` to block until
+/// the file is closed, and terminal editors are run in a terminal emulator if needed. Once the
+/// editor exits, the file is read back and deleted.
+///
+/// Editing is considered aborted if the editor exits unsuccessfully (e.g. with `:cq` in `vim`),
+/// the file is left unchanged, or the text is empty after removing comment lines.
+///
+/// # Errors
+///
+/// - [`Error::NoOpener`] if no editor is found.
+/// - [`Error::NotFound`] if the editor does not exist.
+/// - [`Error::TerminalDetached`] if the editor was run in a terminal emulator that exited right
+///   away without the file being changed.
+/// - [`Error::IO`] if the temporary file cannot be written or read, or the editor cannot be run.
+/// - See [`Error`] for other possible errors.
+pub fn edit_text(initial: &str, options: &EditOptions) -> Result<Option<String>> {
+    edit_text_with(initial, options, &SystemEnvironment)
+}
+
+/// Like [`edit_text`], but finds the editor and terminal emulator in `env`.
+fn edit_text_with<E: Environment + ?Sized>(
+    initial: &str,
+    options: &EditOptions,
+    env: &E,
+) -> Result<Option<String>> {
+    let mut spec = options
+        .chain
+        .resolve_with(env)?
+        .ok_or_else(|| Error::NoOpener {
+            tried: options.chain.tried(),
+        })?;
+    crate::ensure_command_in(env, &spec.program)?;
+
+    let suffix = options
+        .extension
+        .as_ref()
+        .map(|extension| format!(".{extension}"))
+        .unwrap_or_default();
+    // Temporary files are only readable and writable by the current user.
+    let mut file = tempfile::Builder::new()
+        .prefix("open-cmd-")
+        .suffix(&suffix)
+        .tempfile()?;
+    file.write_all(initial.as_bytes())?;
+    // Close the file so that the editor can replace it, but keep deleting it when done.
+    let path = file.into_temp_path();
+
+    if let Some(arg) = crate::editor::blocking_arg(&spec.program) {
+        if !spec.args.iter().any(|other| other == arg) {
+            spec = spec.arg(arg);
+        }
+    }
+    spec = spec.arg(PathOrURI::from(path.to_path_buf()).to_arg()?);
+    let editor = spec.program.clone();
+    if Platform::current() == Platform::Linux {
+        spec = options.chain.wrap_with(spec, env)?;
+    }
+    let terminal = (spec.program != editor).then(|| spec.program.clone());
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!("editing text with {}", spec);
+
+    let started = Instant::now();
+    let status = Command::from(spec).status()?;
+    if let Some(terminal) = terminal {
+        if started.elapsed() < DETACHED_TERMINAL
+            && std::fs::read_to_string(&path).is_ok_and(|edited| edited == initial)
+        {
+            return Err(Error::TerminalDetached(terminal));
+        }
+    }
+    if !status.success() {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("editing was aborted: the editor exited with {}", status);
+        return Ok(None);
+    }
+
+    let edited = std::fs::read_to_string(&path)?;
+    if edited == initial {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("editing was aborted: the text is unchanged");
+        return Ok(None);
+    }
+
+    let text = options.strip(&edited);
+    if text.trim().is_empty() {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("editing was aborted: the text is empty");
+        return Ok(None);
+    }
+    Ok(Some(text))
+}
+
+/// Separates the error comment that [`edit_value`] inserts from the text, like the scissors line
+/// of `git commit --verbose`.
+#[cfg(feature = "serde")]
+const SCISSORS: &str = "------------------------ >8 ------------------------";
+
+/// A format that [`edit_value`] can serialize values as for editing.
+#[cfg(feature = "serde")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValueFormat {
+    /// JSON. Since JSON has no comments, the error comment uses `//` instead.
+    Json,
+    /// TOML.
+    Toml,
+    /// YAML.
+    Yaml,
+}
+
+#[cfg(feature = "serde")]
+impl ValueFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+        }
+    }
+
+    fn comment_prefix(self) -> &'static str {
+        match self {
+            Self::Json => "//",
+            Self::Toml | Self::Yaml => "#",
+        }
+    }
+
+    fn serialize<T: serde::Serialize + ?Sized>(self, value: &T) -> Result<String> {
+        let text = match self {
+            Self::Json => serde_json::to_string_pretty(value)
+                .map(|json| json + "\n")
+                .map_err(|error| error.to_string()),
+            Self::Toml => toml::to_string_pretty(value).map_err(|error| error.to_string()),
+            Self::Yaml => serde_norway::to_string(value).map_err(|error| error.to_string()),
+        };
+        text.map_err(|error| Error::Serialize {
+            format: self,
+            error,
+        })
+    }
+
+    fn deserialize<T: serde::de::DeserializeOwned>(self, text: &str) -> Result<T, String> {
+        match self {
+            Self::Json => serde_json::from_str(text).map_err(|error| error.to_string()),
+            Self::Toml => toml::from_str(text).map_err(|error| error.to_string()),
+            Self::Yaml => serde_norway::from_str(text).map_err(|error| error.to_string()),
+        }
+    }
+
+    /// Returns the comment inserted above the text when it could not be parsed.
+    fn error_comment(self, error: &str) -> String {
+        let prefix = self.comment_prefix();
+        let mut comment = format!(
+            "{prefix} The edited {self} could not be parsed. Fix the error below, or leave the \
+             file\n{prefix} unchanged or empty to cancel.\n{prefix}\n"
+        );
+        for line in error.lines() {
+            comment.push_str(format!("{prefix}   {line}").trim_end());
+            comment.push('\n');
+        }
+        comment
+            + &format!(
+                "{prefix}\n{prefix} Do not modify or remove the line below. Everything above it \
+                 will be removed.\n{prefix} {SCISSORS}\n"
+            )
+    }
+
+    /// Removes the comment inserted by [`ValueFormat::error_comment`] from the top of `text`, if
+    /// it is still there. Other comments are kept.
+    fn strip_error_comment(self, text: &str) -> &str {
+        let prefix = self.comment_prefix();
+        let scissors = format!("{prefix} {SCISSORS}\n");
+        match text.split_once(&scissors) {
+            Some((comment, rest)) if comment.lines().all(|line| line.starts_with(prefix)) => rest,
+            _ => text,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::fmt::Display for ValueFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json => write!(f, "JSON"),
+            Self::Toml => write!(f, "TOML"),
+            Self::Yaml => write!(f, "YAML"),
+        }
+    }
+}
+
+/// Lets the user edit `value` as `format` in their text editor and returns the edited value, or
+/// `None` if they aborted.
+///
+/// The value is edited with [`edit_text`], using the extension of `format`. If the edited text
+/// cannot be parsed, the editor is opened again with the error inserted as a comment at the top,
+/// which is `#` or `//` for JSON, until the text parses or the user aborts. Only that comment is
+/// removed before parsing again, up to a scissors line that marks its end, so comments and
+/// strings in the value itself are kept as the user wrote them.
+///
+/// # Errors
+///
+/// - [`Error::Serialize`] if `value` cannot be serialized as `format`.
+/// - See [`edit_text`] for other possible errors.
+#[cfg(feature = "serde")]
+pub fn edit_value<T>(value: &T, format: ValueFormat, options: &EditOptions) -> Result<Option<T>>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let options = options.clone().extension(format.extension());
+    let mut text = format.serialize(value)?;
+
+    loop {
+        let Some(edited) = edit_text(&text, &options)? else {
+            return Ok(None);
+        };
+        let edited = format.strip_error_comment(&edited);
+        if edited.trim().is_empty() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("editing was aborted: the text is empty");
+            return Ok(None);
+        }
+        match format.deserialize(edited) {
+            Ok(value) => return Ok(Some(value)),
+            Err(error) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("the edited {} could not be parsed: {}", format, error);
+
+                text = format.error_comment(&error) + edited;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Returns options that run `script` with `sh`, with the file as `$1`.
+    fn editor(name: &str, script: &str) -> EditOptions {
+        let var = format!("OPEN_CMD_TEST_EDIT_{name}");
+        std::env::set_var(&var, format!("sh -c '{script}' sh"));
+        EditOptions::new().editor_chain(EditorChain::new().env(var).terminal(true))
+    }
+
+    #[test]
+    fn test_edit_text() {
+        let options = editor("APPEND", r#"echo world >> "$1""#);
+        assert_eq!(
+            edit_text("hello\n", &options).unwrap().as_deref(),
+            Some("hello\nworld\n")
+        );
+    }
+
+    #[test]
+    fn test_abort() {
+        for (name, script) in [
+            ("FAIL", r#"echo changed > "$1"; exit 1"#),
+            ("UNCHANGED", "true"),
+            ("EMPTY", r#": > "$1""#),
+        ] {
+            assert_eq!(
+                edit_text("hello\n", &editor(name, script)).unwrap(),
+                None,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        let options = editor("COMMENTS", r#"printf "fix: typo\n%s" "$(cat "$1")" > "$1""#)
+            .strip_comments("#");
+        assert_eq!(
+            edit_text("# Write a message.\n", &options)
+                .unwrap()
+                .as_deref(),
+            Some("fix: typo\n")
+        );
+
+        let options =
+            editor("ONLY_COMMENTS", r##"echo "# still a comment" >> "$1""##).strip_comments("#");
+        assert_eq!(edit_text("# Write a message.\n", &options).unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_terminal_detached() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        for program in ["nano", "handoff-term"] {
+            let file = dir.path().join(program);
+            std::fs::write(&file, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let terminal = dir.path().join("handoff-term");
+        let env = crate::test_util::env(&[
+            ("OPEN_CMD_TEST_DETACHED", "nano"),
+            ("TERMINAL", terminal.to_str().unwrap()),
+            ("PATH", dir.path().to_str().unwrap()),
+        ]);
+        let options = EditOptions::new().editor_chain(
+            EditorChain::new()
+                .env("OPEN_CMD_TEST_DETACHED")
+                .terminal(false),
+        );
+        assert!(matches!(
+            edit_text_with("hello\n", &options, &env),
+            Err(Error::TerminalDetached(program)) if program == terminal.to_str().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_temp_file() {
+        let options = editor(
+            "TEMP_FILE",
+            r#"printf "%s %s" "${1##*.}" "$(ls -l "$1" | cut -c1-10)" > "$1""#,
+        )
+        .extension("toml");
+        assert_eq!(
+            edit_text("", &options).unwrap().as_deref(),
+            Some("toml -rw-------")
+        );
+    }
+
+    #[test]
+    fn test_no_editor() {
+        let options = EditOptions::new().editor_chain(EditorChain::new());
+        assert!(matches!(
+            edit_text("", &options),
+            Err(Error::NoOpener { tried }) if tried.is_empty()
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Config {
+        name: String,
+        retries: u32,
+    }
+
+    #[cfg(feature = "serde")]
+    const CONFIG: Config = Config {
+        name: String::new(),
+        retries: 1,
+    };
+
+    /// Returns options that use a stub editor, which saves each file it is given as `seen<N>` and
+    /// replaces it with `replies[N]`, or exits unsuccessfully if there is no such reply.
+    #[cfg(feature = "serde")]
+    fn stub(name: &str, replies: &[&str]) -> (tempfile::TempDir, EditOptions) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        for (i, reply) in replies.iter().enumerate() {
+            std::fs::write(dir.path().join(format!("reply{i}")), reply).unwrap();
+        }
+        let script = dir.path().join("editor");
+        std::fs::write(
+            &script,
+            r#"#!/bin/sh
+dir=$(dirname "$0")
+n=$(ls "$dir" | grep -c '^seen')
+cp "$1" "$dir/seen$n"
+[ -f "$dir/reply$n" ] || exit 1
+cp "$dir/reply$n" "$1"
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let var = format!("OPEN_CMD_TEST_VALUE_{name}");
+        std::env::set_var(&var, &script);
+        let options = EditOptions::new().editor_chain(EditorChain::new().env(var).terminal(true));
+        (dir, options)
+    }
+
+    #[cfg(feature = "serde")]
+    fn seen(dir: &tempfile::TempDir, n: usize) -> String {
+        std::fs::read_to_string(dir.path().join(format!("seen{n}"))).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_edit_value_retry() {
+        let fixed = ValueFormat::Toml.error_comment("oops") + "name = \"b\"\nretries = 3\n";
+        let (dir, options) = stub("RETRY", &["name = \"\"\nretries = \n", &fixed]);
+        assert_eq!(
+            edit_value(&CONFIG, ValueFormat::Toml, &options).unwrap(),
+            Some(Config {
+                name: "b".to_string(),
+                retries: 3
+            })
+        );
+        assert_eq!(seen(&dir, 0), "name = \"\"\nretries = 1\n");
+
+        let retry = seen(&dir, 1);
+        assert!(
+            retry.starts_with("# The edited TOML could not be parsed."),
+            "{retry}"
+        );
+        assert!(retry.contains("\n#   "), "{retry}");
+        assert!(
+            retry.ends_with(
+                "# ------------------------ >8 ------------------------\nname = \"\"\nretries = \n"
+            ),
+            "{retry}"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_edit_value_keeps_comments() {
+        let reply = "# A comment\nname = \"\"\"\nintro\n# heading\n\"\"\"\nretries = 2\n";
+        let (_dir, options) = stub("KEEP_COMMENTS", &[reply]);
+        assert_eq!(
+            edit_value(&CONFIG, ValueFormat::Toml, &options).unwrap(),
+            Some(Config {
+                name: "intro\n# heading\n".to_string(),
+                retries: 2
+            })
+        );
+
+        // Only the inserted error comment is removed when retrying.
+        let fixed = ValueFormat::Toml.error_comment("oops") + reply;
+        let (_dir, options) = stub("KEEP_COMMENTS_RETRY", &["retries = \n", &fixed]);
+        assert_eq!(
+            edit_value(&CONFIG, ValueFormat::Toml, &options).unwrap(),
+            Some(Config {
+                name: "intro\n# heading\n".to_string(),
+                retries: 2
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_edit_value_json() {
+        let (dir, options) = stub("JSON", &["{\"name\": \"b\", \"retries\": 2}\n"]);
+        assert_eq!(
+            edit_value(&CONFIG, ValueFormat::Json, &options).unwrap(),
+            Some(Config {
+                name: "b".to_string(),
+                retries: 2
+            })
+        );
+        assert_eq!(seen(&dir, 0), "{\n  \"name\": \"\",\n  \"retries\": 1\n}\n");
+
+        let (dir, options) = stub("JSON_ABORT", &["{\"name\": \"b\""]);
+        assert_eq!(
+            edit_value(&CONFIG, ValueFormat::Json, &options).unwrap(),
+            None
+        );
+        assert!(seen(&dir, 1).starts_with("// The edited JSON could not be parsed."));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_edit_value_yaml() {
+        let (dir, options) = stub("YAML", &["name: ''\nretries: 1\n"]);
+        assert_eq!(
+            edit_value(&CONFIG, ValueFormat::Yaml, &options).unwrap(),
+            None
+        );
+        assert_eq!(seen(&dir, 0), "name: ''\nretries: 1\n");
+
+        let (_dir, options) = stub("YAML_EDIT", &["name: b  # a comment\nretries: 4\n"]);
+        assert_eq!(
+            edit_value(&CONFIG, ValueFormat::Yaml, &options).unwrap(),
+            Some(Config {
+                name: "b".to_string(),
+                retries: 4
+            })
+        );
+    }
+}
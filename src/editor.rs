@@ -0,0 +1,651 @@
+//! Finding text editors and opening files at a line and column in them.
+//!
+//! Each editor has its own syntax for positions, so the program named by the environment is
+//! matched against a list of known editors by its file name. Templates are lists of arguments in
+//! which `{file}`, `{line}`, and `{column}` are replaced with the target and its position.
+
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
+use crate::{CommandSpec, Environment, PathOrURI, Platform, Result, EDITOR_ENV, VISUAL_ENV};
+
+/// A position in a file to open an editor at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Position {
+    /// The line, starting at 1.
+    pub(crate) line: usize,
+    /// The column, starting at 1, if known.
+    pub(crate) column: Option<usize>,
+}
+
+/// The arguments that open a file at a position in a particular editor.
+///
+/// Each argument may contain `{file}`, `{line}`, and `{column}`, which are replaced with the
+/// target and its position. `{{` and `}}` stand for literal braces. See
+/// [`Opener::editor_template`](crate::Opener::editor_template).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EditorTemplate {
+    line: Vec<String>,
+    column: Option<Vec<String>>,
+}
+
+impl EditorTemplate {
+    /// Create a template with the arguments to use when only the line is known, or when the
+    /// editor cannot jump to a column.
+    pub fn new<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            line: args.into_iter().map(Into::into).collect(),
+            column: None,
+        }
+    }
+
+    /// The arguments to use when both the line and column are known.
+    #[must_use]
+    pub fn with_column<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.column = Some(args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Returns the arguments that open `file` at `position`.
+    fn args(&self, file: &str, position: Position) -> Vec<String> {
+        let (args, column) = match (&self.column, position.column) {
+            (Some(args), Some(column)) => (args, column.to_string()),
+            _ => (&self.line, String::new()),
+        };
+        let line = position.line.to_string();
+
+        args.iter()
+            .map(|arg| fill(arg, &[("file", file), ("line", &line), ("column", &column)]))
+            .collect()
+    }
+}
+
+/// Replaces each `{name}` in `template` with its value, and `{{` and `}}` with single braces.
+/// Unknown names are kept as-is.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        result.push_str(&rest[..start]);
+        let marker = &rest[start..];
+
+        if marker.starts_with("{{") || marker.starts_with("}}") {
+            result.push_str(&marker[..1]);
+            rest = &marker[2..];
+            continue;
+        }
+
+        let value = marker.strip_prefix('{').and_then(|inner| {
+            let end = inner.find('}')?;
+            let (_, value) = values.iter().find(|(name, _)| *name == &inner[..end])?;
+            Some((*value, end + 2))
+        });
+        let (value, len) = value.unwrap_or((&marker[..1], 1));
+        result.push_str(value);
+        rest = &marker[len..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Programs that use `+{line}` and can jump to a column with `+call cursor(...)`.
+const VIM: &[&str] = &["vim", "gvim", "mvim", "nvim", "neovim", "vimx"];
+/// Programs that use `+{line}:{column}`.
+const PLUS_COLON: &[&str] = &[
+    "emacs",
+    "emacsclient",
+    "gedit",
+    "gnome-text-editor",
+    "kak",
+    "micro",
+];
+/// Programs that use `+{line},{column}`.
+const PLUS_COMMA: &[&str] = &["nano", "rnano", "ne"];
+/// Programs that use `+{line}` and cannot jump to a column.
+const PLUS: &[&str] = &["vi", "nvi", "elvis", "joe", "mg", "pico"];
+/// Programs that use `{file}:{line}:{column}`.
+const SUFFIX: &[&str] = &[
+    "subl",
+    "sublime_text",
+    "hx",
+    "helix",
+    "zed",
+    "zeditor",
+    "atom",
+];
+/// Programs that use `-g {file}:{line}:{column}`.
+const VSCODE: &[&str] = &[
+    "code",
+    "code-insiders",
+    "codium",
+    "vscodium",
+    "cursor",
+    "windsurf",
+];
+/// Programs that use `--line {line} --column {column}`.
+const JETBRAINS: &[&str] = &[
+    "idea", "idea64", "pycharm", "webstorm", "phpstorm", "clion", "goland", "rubymine", "rider",
+    "studio", "fleet",
+];
+/// Programs that use `--line {line} --column {column}`, as KDE applications do.
+const KATE: &[&str] = &["kate", "kwrite"];
+
+/// Arguments that make GUI editors wait until the file is closed before exiting.
+const BLOCKING_ARGS: &[(&[&str], &str)] = &[
+    (VSCODE, "--wait"),
+    (JETBRAINS, "--wait"),
+    (
+        &["subl", "sublime_text", "zed", "zeditor", "atom", "gedit"],
+        "--wait",
+    ),
+    (&["mate"], "-w"),
+    (&["gvim", "mvim"], "-f"),
+    (&["kate"], "--block"),
+];
+
+/// Returns the argument that makes `program` wait until the file is closed before exiting, if it
+/// is a GUI editor that would otherwise return immediately.
+pub(crate) fn blocking_arg(program: &str) -> Option<&'static str> {
+    let name = program_name(program);
+    BLOCKING_ARGS
+        .iter()
+        .find(|(names, _)| names.contains(&name.as_str()))
+        .map(|(_, arg)| *arg)
+}
+
+/// Returns the file name of `program` without an extension, in lowercase.
+///
+/// Both `/` and `\` are treated as separators, so that Windows paths are also recognized when
+/// generating commands for Windows on another platform.
+pub(crate) fn program_name(program: &str) -> String {
+    let file = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    Path::new(file)
+        .file_stem()
+        .map_or_else(
+            || file.to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        )
+        .to_ascii_lowercase()
+}
+
+/// Returns the built-in template for `program`, matched by its file name without an extension.
+pub(crate) fn known(program: &str) -> Option<EditorTemplate> {
+    let name = program_name(program);
+    let is = |names: &[&str]| names.contains(&name.as_str());
+
+    let template = if is(VIM) {
+        EditorTemplate::new(["+{line}", "{file}"])
+            .with_column(["+call cursor({line},{column})", "{file}"])
+    } else if is(PLUS_COLON) {
+        EditorTemplate::new(["+{line}", "{file}"]).with_column(["+{line}:{column}", "{file}"])
+    } else if is(PLUS_COMMA) {
+        EditorTemplate::new(["+{line}", "{file}"]).with_column(["+{line},{column}", "{file}"])
+    } else if is(PLUS) {
+        EditorTemplate::new(["+{line}", "{file}"])
+    } else if is(SUFFIX) {
+        EditorTemplate::new(["{file}:{line}"]).with_column(["{file}:{line}:{column}"])
+    } else if is(VSCODE) {
+        EditorTemplate::new(["-g", "{file}:{line}"]).with_column(["-g", "{file}:{line}:{column}"])
+    } else if is(JETBRAINS) || is(KATE) {
+        EditorTemplate::new(["--line", "{line}", "{file}"])
+            .with_column(["--line", "{line}", "--column", "{column}", "{file}"])
+    } else if name == "notepad++" {
+        EditorTemplate::new(["-n{line}", "{file}"]).with_column([
+            "-n{line}",
+            "-c{column}",
+            "{file}",
+        ])
+    } else if name == "jed" {
+        EditorTemplate::new(["-g", "{line}", "{file}"])
+    } else if name == "mate" {
+        EditorTemplate::new(["-l", "{line}", "{file}"]).with_column([
+            "-l",
+            "{line}:{column}",
+            "{file}",
+        ])
+    } else {
+        return None;
+    };
+    Some(template)
+}
+
+/// Generate a command that opens the target at `position` with `cmd` and its `args`, using
+/// `template` for the position.
+pub(crate) fn command<S: AsRef<str>>(
+    platform: Platform,
+    cmd: &str,
+    args: &[S],
+    template: &EditorTemplate,
+    position: Position,
+    target: &PathOrURI,
+) -> Result<CommandSpec> {
+    crate::ensure_command_on(platform, cmd)?;
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!("opening {} with {} at {:?}", target, cmd, position);
+
+    Ok(CommandSpec::new(cmd)
+        .args(args.iter().map(AsRef::as_ref))
+        .args(template.args(&target.to_arg()?, position)))
+}
+
+/// The programs tried at the end of the default [`EditorChain`].
+#[cfg(not(target_os = "windows"))]
+const FALLBACK_EDITORS: &[&str] = &["sensible-editor", "editor", "vi", "nano"];
+#[cfg(target_os = "windows")]
+const FALLBACK_EDITORS: &[&str] = &["notepad"];
+
+/// A place to look for the text editor.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Step {
+    /// The command in an environment variable.
+    Env(String),
+    /// The command in an environment variable, only used when a terminal is attached.
+    TerminalEnv(String),
+    /// `core.editor` from the git configuration files.
+    GitConfig,
+    /// A program, used if it exists.
+    Program(String),
+}
+
+/// Where to look for the text editor, in order, like git and other command-line programs do.
+///
+/// The default chain is:
+///
+/// 1. [`VISUAL_ENV`], if a terminal is attached.
+/// 2. [`EDITOR_ENV`].
+/// 3. `core.editor` from the git configuration files.
+/// 4. `sensible-editor`, `editor`, `vi`, and `nano` (`notepad` on Windows), whichever exists
+///    first.
+///
+/// Variables and `core.editor` hold a command, which is split into words like a POSIX shell
+/// would. Steps that are unset or empty are skipped. Use [`EditorChain::app_env`] to put an
+/// application-specific variable first, or [`EditorChain::new`] to build a chain from scratch.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EditorChain {
+    steps: Vec<Step>,
+    terminal: Option<bool>,
+    wrap_in_terminal: bool,
+    git_config_files: Option<Vec<PathBuf>>,
+}
+
+impl Default for EditorChain {
+    fn default() -> Self {
+        FALLBACK_EDITORS.iter().fold(
+            Self::new()
+                .terminal_env(VISUAL_ENV)
+                .env(EDITOR_ENV)
+                .git_config(),
+            |chain, program| chain.program(*program),
+        )
+    }
+}
+
+impl EditorChain {
+    /// Create an empty chain.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            steps: Vec::new(),
+            terminal: None,
+            wrap_in_terminal: true,
+            git_config_files: None,
+        }
+    }
+
+    /// Look for the editor in the variable `var` before any other step, e.g. `MYAPP_EDITOR`.
+    #[must_use]
+    pub fn app_env<S: Into<String>>(mut self, var: S) -> Self {
+        self.steps.insert(0, Step::Env(var.into()));
+        self
+    }
+
+    /// Look for the editor in the variable `var`.
+    #[must_use]
+    pub fn env<S: Into<String>>(mut self, var: S) -> Self {
+        self.steps.push(Step::Env(var.into()));
+        self
+    }
+
+    /// Look for the editor in the variable `var`, but only if a terminal is attached, as is the
+    /// convention for [`VISUAL_ENV`].
+    #[must_use]
+    pub fn terminal_env<S: Into<String>>(mut self, var: S) -> Self {
+        self.steps.push(Step::TerminalEnv(var.into()));
+        self
+    }
+
+    /// Look for the editor in `core.editor` in the git configuration files.
+    #[must_use]
+    pub fn git_config(mut self) -> Self {
+        self.steps.push(Step::GitConfig);
+        self
+    }
+
+    /// Use `program` as the editor if it exists on the `PATH`.
+    #[must_use]
+    pub fn program<S: Into<String>>(mut self, program: S) -> Self {
+        self.steps.push(Step::Program(program.into()));
+        self
+    }
+
+    /// Whether a terminal is attached, instead of checking whether the standard input and output
+    /// are terminals.
+    #[must_use]
+    pub fn terminal(mut self, terminal: bool) -> Self {
+        self.terminal = Some(terminal);
+        self
+    }
+
+    /// Whether to run editors that need a terminal, such as `vim`, in a terminal emulator when no
+    /// terminal is attached. Defaults to `true`. This only applies to Linux and other Unix-like
+    /// systems besides macOS.
+    ///
+    /// Editors need a terminal if they are in a built-in list or their desktop entry sets
+    /// `Terminal=true`. The emulator is taken from [`TERMINAL_ENV`](crate::TERMINAL_ENV), or else
+    /// is the first of `xdg-terminal-exec`, `x-terminal-emulator`, `foot`, `kitty`, `alacritty`,
+    /// `wezterm`, `gnome-terminal`, `konsole`, `xfce4-terminal`, and `xterm` that exists.
+    #[must_use]
+    pub fn wrap_in_terminal(mut self, wrap: bool) -> Self {
+        self.wrap_in_terminal = wrap;
+        self
+    }
+
+    /// The git configuration files to read `core.editor` from, from lowest to highest precedence.
+    ///
+    /// Defaults to the system, global, and repository files that git reads.
+    #[must_use]
+    pub fn git_config_files<I, P>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.git_config_files = Some(files.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Returns the command of the first editor found, without a target, or `None` if no step
+    /// applies. Variables, home directories, and the `PATH` are read from `env`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::ParseCommand`](crate::Error::ParseCommand) if a command has malformed quoting.
+    pub fn resolve_with<E: Environment + ?Sized>(&self, env: &E) -> Result<Option<CommandSpec>> {
+        for step in &self.steps {
+            #[cfg(feature = "tracing")]
+            tracing::trace!("looking for editor in {}", describe(step));
+
+            let found = match step {
+                Step::TerminalEnv(_) if !self.is_terminal() => {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(
+                        "skipping {} because no terminal is attached",
+                        describe(step)
+                    );
+                    None
+                }
+                Step::Env(var) | Step::TerminalEnv(var) => match env.var(var) {
+                    Some(value) => parse(var, &value.to_string_lossy())?,
+                    None => None,
+                },
+                Step::GitConfig => {
+                    let files = self
+                        .git_config_files
+                        .clone()
+                        .unwrap_or_else(|| crate::git_config::default_files(env));
+                    match crate::git_config::core_editor(&files) {
+                        Some(value) => parse("core.editor", &value)?,
+                        None => None,
+                    }
+                }
+                Step::Program(program) => env
+                    .which(program)
+                    .is_ok()
+                    .then(|| CommandSpec::new(program)),
+            };
+
+            if let Some(spec) = found {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("using editor {} from {}", spec, describe(step));
+                return Ok(Some(spec));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Runs `spec`, which opens a target with an editor found by this chain, in a terminal
+    /// emulator if needed. See [`EditorChain::wrap_in_terminal`].
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::NoOpener`](crate::Error::NoOpener) if no terminal emulator is found.
+    /// - [`Error::ParseCommand`](crate::Error::ParseCommand) if
+    ///   [`TERMINAL_ENV`](crate::TERMINAL_ENV) has malformed quoting.
+    pub(crate) fn wrap_with<E: Environment + ?Sized>(
+        &self,
+        spec: CommandSpec,
+        env: &E,
+    ) -> Result<CommandSpec> {
+        if !self.wrap_in_terminal
+            || self.is_terminal()
+            || !crate::terminal::needs_terminal(&spec, &crate::desktop_entry::data_dirs(env))
+        {
+            return Ok(spec);
+        }
+        crate::terminal::wrap(spec, env)
+    }
+
+    /// Returns the steps, as listed in [`Error::NoOpener`](crate::Error::NoOpener).
+    pub(crate) fn tried(&self) -> Vec<String> {
+        self.steps.iter().map(describe).collect()
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.terminal
+            .unwrap_or_else(|| std::io::stdin().is_terminal() && std::io::stdout().is_terminal())
+    }
+}
+
+/// Returns a description of where `step` looks for the editor.
+fn describe(step: &Step) -> String {
+    match step {
+        Step::Env(var) | Step::TerminalEnv(var) => format!("${var}"),
+        Step::GitConfig => "core.editor".to_string(),
+        Step::Program(program) => program.clone(),
+    }
+}
+
+/// Splits a command read from `source`.
+fn parse(source: &str, value: &str) -> Result<Option<CommandSpec>> {
+    Ok(crate::split_command(source, value)?.map(|(cmd, args)| CommandSpec::new(cmd).args(args)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::env;
+
+    fn args(program: &str, line: usize, column: Option<usize>) -> Vec<String> {
+        known(program)
+            .unwrap()
+            .args("src/lib.rs", Position { line, column })
+    }
+
+    #[test]
+    fn test_known() {
+        assert_eq!(args("vim", 42, None), ["+42", "src/lib.rs"]);
+        assert_eq!(
+            args("/usr/bin/nvim", 42, Some(7)),
+            ["+call cursor(42,7)", "src/lib.rs"]
+        );
+        assert_eq!(args("emacs", 42, Some(7)), ["+42:7", "src/lib.rs"]);
+        assert_eq!(args("code", 42, Some(7)), ["-g", "src/lib.rs:42:7"]);
+        assert_eq!(args("subl", 42, Some(7)), ["src/lib.rs:42:7"]);
+        assert_eq!(args("nano", 42, Some(7)), ["+42,7", "src/lib.rs"]);
+        assert_eq!(args("hx", 42, None), ["src/lib.rs:42"]);
+        assert_eq!(args("vi", 42, Some(7)), ["+42", "src/lib.rs"]);
+        assert_eq!(args("jed", 42, Some(7)), ["-g", "42", "src/lib.rs"]);
+        assert_eq!(args("idea", 42, None), ["--line", "42", "src/lib.rs"]);
+        assert_eq!(
+            args(r"C:\Program Files\Notepad++\notepad++.exe", 42, Some(7)),
+            ["-n42", "-c7", "src/lib.rs"]
+        );
+        assert_eq!(args("Code.EXE", 1, None), ["-g", "src/lib.rs:1"]);
+        assert!(known("surely-not-an-editor").is_none());
+        assert!(known("ed").is_none());
+    }
+
+    #[test]
+    fn test_blocking_arg() {
+        assert_eq!(blocking_arg("/usr/bin/code"), Some("--wait"));
+        assert_eq!(blocking_arg("mate"), Some("-w"));
+        assert_eq!(blocking_arg("gvim"), Some("-f"));
+        assert_eq!(blocking_arg("vim"), None);
+    }
+
+    #[test]
+    fn test_fill() {
+        let values = [("file", "a b.txt"), ("line", "3")];
+        assert_eq!(fill("{file}:{line}", &values), "a b.txt:3");
+        assert_eq!(fill("{{line}} {line}", &values), "{line} 3");
+        assert_eq!(fill("{unknown}{line}", &values), "{unknown}3");
+        assert_eq!(fill("{line", &values), "{line");
+    }
+
+    #[test]
+    fn test_command() {
+        let target = PathOrURI::from(PathBuf::from("-notes.md"));
+        let spec = command(
+            Platform::MacOS,
+            "code",
+            &["--wait"],
+            &known("code").unwrap(),
+            Position {
+                line: 3,
+                column: Some(1),
+            },
+            &target,
+        )
+        .unwrap();
+        assert_eq!(
+            spec,
+            CommandSpec::new("code").args(["--wait", "-g", "./-notes.md:3:1"])
+        );
+    }
+
+    #[test]
+    fn test_chain_env() {
+        let chain = EditorChain::default()
+            .app_env("MYAPP_EDITOR")
+            .git_config_files(Vec::<PathBuf>::new());
+        let vars = env(&[("VISUAL", "code --wait"), ("EDITOR", "vim")]);
+
+        assert_eq!(
+            chain.clone().terminal(true).resolve_with(&vars).unwrap(),
+            Some(CommandSpec::new("code").arg("--wait"))
+        );
+        assert_eq!(
+            chain.clone().terminal(false).resolve_with(&vars).unwrap(),
+            Some(CommandSpec::new("vim"))
+        );
+
+        let vars = env(&[("MYAPP_EDITOR", "hx"), ("EDITOR", "vim")]);
+        assert_eq!(
+            chain.resolve_with(&vars).unwrap(),
+            Some(CommandSpec::new("hx"))
+        );
+    }
+
+    #[test]
+    fn test_chain_git_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config");
+        std::fs::write(&config, "[core]\n\teditor = \"emacs -nw\"\n").unwrap();
+
+        let chain = EditorChain::default()
+            .terminal(false)
+            .git_config_files([&config]);
+        assert_eq!(
+            chain.resolve_with(&env(&[("EDITOR", "")])).unwrap(),
+            Some(CommandSpec::new("emacs").arg("-nw"))
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_chain_programs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let nano = dir.path().join("nano");
+        std::fs::write(&nano, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&nano, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let chain = EditorChain::default()
+            .terminal(false)
+            .git_config_files(Vec::<PathBuf>::new());
+        let path = dir.path().to_str().unwrap();
+        assert_eq!(
+            chain.resolve_with(&env(&[("PATH", path)])).unwrap(),
+            Some(CommandSpec::new("nano"))
+        );
+        assert_eq!(chain.resolve_with(&env(&[("PATH", "")])).unwrap(), None);
+        assert_eq!(
+            chain.tried(),
+            [
+                "$VISUAL",
+                "$EDITOR",
+                "core.editor",
+                "sensible-editor",
+                "editor",
+                "vi",
+                "nano"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chain_wrap_in_terminal() {
+        let spec = CommandSpec::new("vim").arg("notes.md");
+        let vars = env(&[("TERMINAL", "xterm")]);
+        let chain = EditorChain::default();
+
+        assert_eq!(
+            chain
+                .clone()
+                .terminal(false)
+                .wrap_with(spec.clone(), &vars)
+                .unwrap(),
+            CommandSpec::new("xterm").args(["-e", "vim", "notes.md"])
+        );
+        assert_eq!(
+            chain
+                .clone()
+                .terminal(true)
+                .wrap_with(spec.clone(), &vars)
+                .unwrap(),
+            spec
+        );
+        assert_eq!(
+            chain
+                .terminal(false)
+                .wrap_in_terminal(false)
+                .wrap_with(spec.clone(), &vars)
+                .unwrap(),
+            spec
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Detecting files that the system handler may run instead of opening.
+
+use std::{fs::File, io::Read, path::Path};
+
+/// Extensions of files that are run, installed, or launched when opened, compared
+/// case-insensitively.
+const EXTENSIONS: &[&str] = &[
+    "app",
+    "appimage",
+    "application",
+    "bash",
+    "bat",
+    "bin",
+    "cmd",
+    "com",
+    "command",
+    "cpl",
+    "csh",
+    "desktop",
+    "exe",
+    "fish",
+    "gadget",
+    "hta",
+    "inf",
+    "jar",
+    "js",
+    "jse",
+    "ksh",
+    "lnk",
+    "msc",
+    "msi",
+    "pif",
+    "pl",
+    "ps1",
+    "py",
+    "reg",
+    "run",
+    "scf",
+    "scr",
+    "sh",
+    "tool",
+    "url",
+    "vbe",
+    "vbs",
+    "wsf",
+    "wsh",
+    "zsh",
+];
+
+/// Magic bytes at the start of executable files.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x7fELF", "ELF binary"),
+    (b"#!", "script with a shebang"),
+    (b"MZ", "Windows executable"),
+    (b"\xfe\xed\xfa\xce", "Mach-O binary"),
+    (b"\xfe\xed\xfa\xcf", "Mach-O binary"),
+    (b"\xce\xfa\xed\xfe", "Mach-O binary"),
+    (b"\xcf\xfa\xed\xfe", "Mach-O binary"),
+    (b"\xca\xfe\xba\xbe", "universal Mach-O binary"),
+];
+
+/// Filesystems that cannot store Unix permissions and show every file as executable, such as
+/// Windows drives mounted in WSL.
+#[cfg(target_os = "linux")]
+const PERMISSIONLESS_FILESYSTEMS: &[&str] = &[
+    "9p", "drvfs", "exfat", "fuseblk", "msdos", "ntfs", "ntfs3", "vfat",
+];
+
+/// How many bytes to read when looking for magic bytes and desktop entry groups.
+const HEADER_LEN: u64 = 4096;
+
+/// Returns why `path` may be run instead of opened, if it looks like an executable.
+///
+/// The extension is always checked. If `local` is set, the file is also checked for magic bytes,
+/// and files without an extension for an executable bit; files that cannot be read are not
+/// considered executable. The executable bit is not trusted on files with an extension or on
+/// filesystems that cannot store it, which show every file as executable.
+pub(crate) fn reason(path: &Path, local: bool) -> Option<String> {
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        if EXTENSIONS
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(ext))
+        {
+            return Some(format!("it has the extension .{ext}"));
+        }
+    }
+
+    if !local || !path.is_file() {
+        return None;
+    }
+
+    if path.extension().is_none() && has_exec_bit(path) {
+        return Some("it is marked executable".to_string());
+    }
+
+    let mut header = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(HEADER_LEN).read_to_end(&mut header))
+        .ok()?;
+
+    if let Some((_, kind)) = MAGIC.iter().find(|(magic, _)| header.starts_with(magic)) {
+        return Some(format!("it is a {kind}"));
+    }
+    if String::from_utf8_lossy(&header)
+        .lines()
+        .any(|line| line.trim() == "[Desktop Entry]")
+    {
+        return Some("it is a desktop entry".to_string());
+    }
+
+    None
+}
+
+#[cfg(unix)]
+fn has_exec_bit(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|meta| meta.permissions().mode() & 0o111 != 0)
+        && stores_permissions(path)
+}
+
+/// Returns whether the filesystem that `path` is on stores Unix permissions.
+#[cfg(target_os = "linux")]
+fn stores_permissions(path: &Path) -> bool {
+    let (Ok(path), Ok(mounts)) = (
+        path.canonicalize(),
+        std::fs::read_to_string("/proc/self/mounts"),
+    ) else {
+        return true;
+    };
+    filesystem_type(&mounts, &path).is_none_or(|fs| !PERMISSIONLESS_FILESYSTEMS.contains(&fs))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn stores_permissions(_path: &Path) -> bool {
+    true
+}
+
+/// Returns the type of the filesystem that the absolute `path` is on, from the contents of
+/// `/proc/self/mounts`.
+#[cfg(target_os = "linux")]
+fn filesystem_type<'a>(mounts: &'a str, path: &Path) -> Option<&'a str> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let fs = fields.next()?;
+            path.starts_with(&mount_point)
+                .then_some((mount_point.len(), fs))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, fs)| fs)
+}
+
+#[cfg(not(unix))]
+fn has_exec_bit(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_with(dir: &Path, name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_extensions() {
+        for name in [
+            "run.sh",
+            "setup.EXE",
+            "app.desktop",
+            "x.bat",
+            "y.command",
+            "z.jar",
+        ] {
+            assert!(reason(Path::new(name), false).is_some(), "{name}");
+        }
+        assert!(reason(Path::new("notes.txt"), false).is_none());
+        assert!(reason(Path::new("Makefile"), false).is_none());
+    }
+
+    #[test]
+    fn test_magic_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, contents) in [
+            ("elf", &b"\x7fELF\x02\x01\x01"[..]),
+            ("script", b"#!/bin/sh\necho hi\n"),
+            ("pe", b"MZ\x90\x00"),
+            ("entry", b"# comment\n\n[Desktop Entry]\nExec=true\n"),
+        ] {
+            let path = file_with(dir.path(), name, contents);
+            assert!(reason(&path, true).is_some(), "{name}");
+            assert!(reason(&path, false).is_none(), "{name}");
+        }
+
+        let text = file_with(dir.path(), "notes", b"just some text\n");
+        assert!(reason(&text, true).is_none());
+        assert!(reason(dir.path(), true).is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exec_bit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = file_with(dir.path(), "tool", b"data");
+        assert!(reason(&path, true).is_none());
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(reason(&path, true).is_some());
+
+        // Windows drives in WSL show every file as executable.
+        let pdf = file_with(dir.path(), "report.pdf", b"%PDF-1.7\n");
+        std::fs::set_permissions(&pdf, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(reason(&pdf, true).is_none());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_filesystem_type() {
+        let mounts = "/dev/sdc / ext4 rw,relatime 0 0\n\
+                      C:\\134 /mnt/c 9p rw,aname=drvfs 0 0\n\
+                      D:\\134 /mnt/my\\040drive drvfs rw 0 0\n";
+        let fs = |path: &str| filesystem_type(mounts, Path::new(path));
+        assert_eq!(fs("/home/me/tool"), Some("ext4"));
+        assert_eq!(fs("/mnt/c/Users/me/tool"), Some("9p"));
+        assert_eq!(fs("/mnt/my drive/tool"), Some("drvfs"));
+        assert_eq!(fs("/mnt/cd/tool"), Some("ext4"));
+    }
+}
@@ -0,0 +1,312 @@
+//! Expanding `~` and environment variables in paths.
+
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    hash::BuildHasher,
+    path::{is_separator, Path, PathBuf},
+};
+
+use crate::{Error, Result};
+
+/// The environment that `~` and variables in paths are expanded from, and that programs are looked
+/// up in.
+///
+/// [`SystemEnvironment`] reads the environment of the current process. A [`HashMap`] of
+/// variables can be used instead, e.g. in tests.
+pub trait Environment {
+    /// Returns the value of the variable `name`, if it is set.
+    fn var(&self, name: &str) -> Option<OsString>;
+
+    /// Returns the home directory of `user`, or of the current user if `None`.
+    ///
+    /// By default, the current user's home directory is read from `HOME`, or `USERPROFILE` on
+    /// Windows, and other users' home directories are unknown.
+    fn home_dir(&self, user: Option<&str>) -> Option<PathBuf> {
+        match user {
+            None => current_home(self),
+            Some(_) => None,
+        }
+    }
+
+    /// Returns the full path of `program`, searched for on the `PATH` like a shell does.
+    ///
+    /// By default, the `PATH` from [`Environment::var`] is searched, and relative paths are
+    /// resolved against the current directory.
+    ///
+    /// # Errors
+    ///
+    /// If `program` cannot be found.
+    fn which(&self, program: &str) -> which::Result<PathBuf> {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        which::which_in(program, self.var("PATH"), cwd)
+    }
+
+    /// Returns the contents of the file at `path`, such as `/etc/wsl.conf`, or `None` if it cannot
+    /// be read.
+    ///
+    /// By default, no files can be read.
+    fn read_file(&self, _path: &Path) -> Option<String> {
+        None
+    }
+
+    /// Returns whether this is a Linux system running inside the Windows Subsystem for Linux.
+    ///
+    /// By default, this checks whether `WSL_DISTRO_NAME` or `WSL_INTEROP` is set.
+    fn is_wsl(&self) -> bool {
+        self.var("WSL_DISTRO_NAME").is_some() || self.var("WSL_INTEROP").is_some()
+    }
+}
+
+/// The environment of the current process.
+///
+/// Other users' home directories are read from `/etc/passwd` on Unix systems, and WSL is also
+/// detected from the kernel release.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemEnvironment;
+
+impl Environment for SystemEnvironment {
+    fn var(&self, name: &str) -> Option<OsString> {
+        std::env::var_os(name)
+    }
+
+    fn home_dir(&self, user: Option<&str>) -> Option<PathBuf> {
+        match user {
+            None => current_home(self),
+            Some(user) => passwd_home(user),
+        }
+    }
+
+    fn which(&self, program: &str) -> which::Result<PathBuf> {
+        which::which(program)
+    }
+
+    fn read_file(&self, path: &Path) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
+
+    fn is_wsl(&self) -> bool {
+        crate::wsl::is_wsl()
+    }
+}
+
+impl<S: BuildHasher> Environment for HashMap<String, String, S> {
+    fn var(&self, name: &str) -> Option<OsString> {
+        self.get(name).map(OsString::from)
+    }
+}
+
+/// Returns the current user's home directory from `HOME` or `USERPROFILE`.
+fn current_home<E: Environment + ?Sized>(env: &E) -> Option<PathBuf> {
+    env.var("HOME")
+        .or_else(|| env.var("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Looks up the home directory of `user` in `/etc/passwd`.
+#[cfg(unix)]
+fn passwd_home(user: &str) -> Option<PathBuf> {
+    std::fs::read_to_string("/etc/passwd")
+        .ok()?
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() >= 6 && fields[0] == user)
+        .map(|fields| PathBuf::from(fields[5]))
+}
+
+#[cfg(not(unix))]
+fn passwd_home(_user: &str) -> Option<PathBuf> {
+    None
+}
+
+/// Returns whether `c` may be part of a variable name in `$NAME` or `${NAME}`.
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Returns whether `name` is a valid name in `$NAME` or `${NAME}`.
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(is_name_char)
+}
+
+/// Returns whether `name` is a valid name in `%NAME%`, which may contain parentheses, as in
+/// `%ProgramFiles(x86)%`.
+fn is_windows_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| is_name_char(c) || matches!(c, '(' | ')'))
+}
+
+/// Expands a leading `~` or `~user` and the variables `$NAME`, `${NAME}`, and `%NAME%` in `path`.
+///
+/// Text that does not form one of these, such as a lone `$` or `%`, is kept as-is. Paths that are
+/// not valid Unicode are returned unchanged.
+///
+/// # Errors
+///
+/// - [`Error::UnknownHome`] if the home directory for `~` cannot be determined.
+/// - [`Error::UndefinedVar`] if a variable is not set.
+pub(crate) fn expand<E: Environment + ?Sized>(path: &Path, env: &E) -> Result<PathBuf> {
+    let Some(mut rest) = path.to_str() else {
+        return Ok(path.to_path_buf());
+    };
+    let mut expanded = OsString::new();
+
+    if let Some(tilde) = rest.strip_prefix('~') {
+        let end = tilde.find(is_separator).unwrap_or(tilde.len());
+        let user = Some(&tilde[..end]).filter(|user| !user.is_empty());
+        let home = env.home_dir(user).ok_or_else(|| Error::UnknownHome {
+            user: user.map(ToString::to_string),
+            path: path.to_path_buf(),
+        })?;
+        expanded.push(home);
+        rest = &tilde[end..];
+    }
+
+    let var = |name: &str| {
+        env.var(name).ok_or_else(|| Error::UndefinedVar {
+            var: name.to_string(),
+            path: path.to_path_buf(),
+        })
+    };
+
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded.push(&rest[..start]);
+        let marker = &rest[start..];
+
+        let (name, len) = if let Some(braced) = marker.strip_prefix("${") {
+            match braced.find('}') {
+                Some(end) if is_name(&braced[..end]) => (Some(&braced[..end]), end + 3),
+                _ => (None, 1),
+            }
+        } else if let Some(bare) = marker.strip_prefix('$') {
+            let end = bare.find(|c| !is_name_char(c)).unwrap_or(bare.len());
+            if is_name(&bare[..end]) {
+                (Some(&bare[..end]), end + 1)
+            } else {
+                (None, 1)
+            }
+        } else {
+            let percent = &marker[1..];
+            match percent.find('%') {
+                Some(end) if is_windows_name(&percent[..end]) => (Some(&percent[..end]), end + 2),
+                _ => (None, 1),
+            }
+        };
+
+        match name {
+            Some(name) => expanded.push(var(name)?),
+            None => expanded.push(&marker[..len]),
+        }
+        rest = &marker[len..];
+    }
+    expanded.push(rest);
+
+    Ok(PathBuf::from(expanded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> HashMap<String, String> {
+        crate::test_util::env(&[
+            ("HOME", "/home/me"),
+            ("XDG_DOCUMENTS_DIR", "/home/me/Documents"),
+            ("USERPROFILE", r"C:\Users\me"),
+            ("ProgramFiles(x86)", r"C:\Program Files (x86)"),
+            ("EMPTY", ""),
+        ])
+    }
+
+    fn expanded(path: &str) -> PathBuf {
+        expand(Path::new(path), &env()).unwrap()
+    }
+
+    #[test]
+    fn test_tilde() {
+        assert_eq!(expanded("~"), Path::new("/home/me"));
+        assert_eq!(expanded("~/notes.md"), Path::new("/home/me/notes.md"));
+        assert_eq!(expanded("notes/~/a"), Path::new("notes/~/a"));
+        assert!(matches!(
+            expand(Path::new("~alice/shared"), &env()),
+            Err(Error::UnknownHome { user: Some(user), .. }) if user == "alice"
+        ));
+        assert!(matches!(
+            expand(Path::new("~/notes.md"), &HashMap::<String, String>::new()),
+            Err(Error::UnknownHome { user: None, .. })
+        ));
+    }
+
+    #[test]
+    fn test_tilde_user() {
+        #[derive(Debug)]
+        struct Users;
+
+        impl Environment for Users {
+            fn var(&self, _name: &str) -> Option<OsString> {
+                None
+            }
+
+            fn home_dir(&self, user: Option<&str>) -> Option<PathBuf> {
+                user.map(|user| Path::new("/home").join(user))
+            }
+        }
+
+        assert_eq!(
+            expand(Path::new("~alice/shared"), &Users).unwrap(),
+            Path::new("/home/alice/shared")
+        );
+    }
+
+    #[test]
+    fn test_vars() {
+        assert_eq!(
+            expanded("$XDG_DOCUMENTS_DIR/report.pdf"),
+            Path::new("/home/me/Documents/report.pdf")
+        );
+        assert_eq!(expanded("${HOME}_backup/x"), Path::new("/home/me_backup/x"));
+        assert_eq!(
+            expanded(r"%USERPROFILE%\Desktop"),
+            Path::new(r"C:\Users\me\Desktop")
+        );
+        assert_eq!(
+            expanded(r"%ProgramFiles(x86)%\App"),
+            Path::new(r"C:\Program Files (x86)\App")
+        );
+        assert_eq!(expanded("a${EMPTY}b"), Path::new("ab"));
+    }
+
+    #[test]
+    fn test_literals() {
+        for path in [
+            "100% done",
+            "cost $5",
+            "a$",
+            "${not closed",
+            "${1}",
+            "50%",
+            "%not a var%",
+        ] {
+            assert_eq!(expanded(path), Path::new(path), "{path}");
+        }
+        assert_eq!(expanded("100% of %HOME%"), Path::new("100% of /home/me"));
+    }
+
+    #[test]
+    fn test_undefined() {
+        for path in ["$MISSING/x", "${MISSING}", "%MISSING%"] {
+            assert!(
+                matches!(
+                    expand(Path::new(path), &env()),
+                    Err(Error::UndefinedVar { var, .. }) if var == "MISSING"
+                ),
+                "{path}"
+            );
+        }
+    }
+}
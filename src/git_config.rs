@@ -0,0 +1,283 @@
+//! Reading `core.editor` from git configuration files.
+//!
+//! Only the subset of the format needed for this is supported: sections, quoted values, escapes,
+//! comments, and line continuations. `[include]` and `[includeIf]` sections are ignored.
+
+use std::path::{Path, PathBuf};
+
+use crate::Environment;
+
+/// Returns the git configuration files to read, from lowest to highest precedence.
+///
+/// These are the system file, the global files, and the file of the repository containing the
+/// current directory, if any, which may be a worktree or submodule. `GIT_CONFIG_SYSTEM`, `GIT_CONFIG_NOSYSTEM`, `GIT_CONFIG_GLOBAL`,
+/// and `XDG_CONFIG_HOME` are respected like git does.
+pub(crate) fn default_files<E: Environment + ?Sized>(env: &E) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    if env.var("GIT_CONFIG_NOSYSTEM").is_none() {
+        match env.var("GIT_CONFIG_SYSTEM") {
+            Some(system) => files.push(PathBuf::from(system)),
+            None if cfg!(unix) => files.push(PathBuf::from("/etc/gitconfig")),
+            None => {}
+        }
+    }
+
+    if let Some(global) = env.var("GIT_CONFIG_GLOBAL") {
+        files.push(PathBuf::from(global));
+    } else {
+        let home = env.home_dir(None);
+        let xdg = env
+            .var("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".config")));
+        files.extend(xdg.map(|dir| dir.join("git").join("config")));
+        files.extend(home.map(|home| home.join(".gitconfig")));
+    }
+
+    if let Ok(dir) = std::env::current_dir() {
+        files.extend(repository_config(&dir));
+    }
+
+    files
+}
+
+/// Returns the configuration file of the repository containing `dir`, if any.
+///
+/// Like git, this follows `.git` files that point to the repository with `gitdir:`, as used by
+/// worktrees and submodules, and a `commondir` file in it, which worktrees use to share the
+/// configuration of the main repository.
+fn repository_config(dir: &Path) -> Option<PathBuf> {
+    let git_dir = dir.ancestors().find_map(|dir| {
+        let git = dir.join(".git");
+        if git.is_dir() {
+            return Some(git);
+        }
+        let contents = std::fs::read_to_string(&git).ok()?;
+        let target = contents.trim_end().strip_prefix("gitdir:")?.trim();
+        Some(dir.join(target))
+    })?;
+
+    let common_dir = match std::fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common_dir) => git_dir.join(common_dir.trim_end()),
+        Err(_) => git_dir,
+    };
+    Some(common_dir.join("config"))
+}
+
+/// Returns the `core.editor` set in the file with the highest precedence. Files that cannot be
+/// read are skipped.
+pub(crate) fn core_editor(files: &[PathBuf]) -> Option<String> {
+    files
+        .iter()
+        .rev()
+        .filter_map(|file| read(file))
+        .find_map(|contents| get(&contents, "core", "editor"))
+}
+
+fn read(file: &Path) -> Option<String> {
+    #[cfg(feature = "tracing")]
+    tracing::trace!("reading git config file {}", file.display());
+
+    std::fs::read_to_string(file).ok()
+}
+
+/// Returns the last value of `key` in `section`, which must not have a subsection, in the
+/// contents of a git configuration file.
+fn get(contents: &str, section: &str, key: &str) -> Option<String> {
+    let mut current = String::new();
+    let mut value = None;
+    let mut lines = contents.lines();
+
+    while let Some(line) = lines.next() {
+        let mut line = line.trim_start();
+
+        if let Some(header) = line.strip_prefix('[') {
+            let Some(end) = header.find(']') else {
+                continue;
+            };
+            current = header[..end].trim().to_ascii_lowercase();
+            line = header[end + 1..].trim_start();
+        }
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+
+        let (name, rest) = line.split_once('=').unwrap_or((line, ""));
+        let name = name.trim();
+        if !current.eq_ignore_ascii_case(section) || !name.eq_ignore_ascii_case(key) {
+            continue;
+        }
+
+        // Values may continue on the next line after a trailing backslash.
+        let mut raw = rest.to_string();
+        while raw.ends_with('\\') && !raw.ends_with("\\\\") {
+            raw.pop();
+            match lines.next() {
+                Some(next) => raw.push_str(next),
+                None => break,
+            }
+        }
+        value = Some(unquote(&raw));
+    }
+
+    value
+}
+
+/// Parses a raw value: removes comments and surrounding whitespace outside of quotes, removes
+/// quotes, and handles escapes.
+fn unquote(raw: &str) -> String {
+    let mut value = String::new();
+    let mut pending_space = String::new();
+    let mut quoted = false;
+    let mut chars = raw.trim_start().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                value.push_str(&pending_space);
+                pending_space.clear();
+                quoted = !quoted;
+            }
+            '\\' => {
+                value.push_str(&pending_space);
+                pending_space.clear();
+                match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('b') => {
+                        value.pop();
+                    }
+                    Some(c) => value.push(c),
+                    None => {}
+                }
+            }
+            '#' | ';' if !quoted => break,
+            c if c.is_whitespace() && !quoted => pending_space.push(c),
+            c => {
+                value.push_str(&pending_space);
+                pending_space.clear();
+                value.push(c);
+            }
+        }
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::env;
+
+    #[test]
+    fn test_get() {
+        let contents = r#"
+# A comment
+[user]
+    name = Someone
+[core]
+    autocrlf = false
+    editor = vim
+[Core]
+    Editor = "code --wait" ; trailing comment
+[core "sub"]
+    editor = nano
+"#;
+        assert_eq!(get(contents, "core", "editor").unwrap(), "code --wait");
+        assert_eq!(get(contents, "user", "name").unwrap(), "Someone");
+        assert_eq!(get(contents, "core", "pager"), None);
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(
+            unquote(r#" "C:\\Program Files\\Vim\\gvim.exe" -f  "#),
+            r"C:\Program Files\Vim\gvim.exe -f"
+        );
+        assert_eq!(unquote("emacs -nw # comment"), "emacs -nw");
+        assert_eq!(unquote(r#"say "a # b""#), "say a # b");
+        assert_eq!(unquote(r#"a \"b\""#), r#"a "b""#);
+    }
+
+    #[test]
+    fn test_continuation() {
+        let contents = "[core]\n\teditor = my-editor \\\n--flag\n";
+        assert_eq!(get(contents, "core", "editor").unwrap(), "my-editor --flag");
+    }
+
+    #[test]
+    fn test_core_editor() {
+        let dir = tempfile::tempdir().unwrap();
+        let global = dir.path().join("global");
+        let local = dir.path().join("local");
+        std::fs::write(&global, "[core]\neditor = vim\n").unwrap();
+        std::fs::write(&local, "[core]\nbare = false\n").unwrap();
+
+        let files = [dir.path().join("missing"), global.clone(), local.clone()];
+        assert_eq!(core_editor(&files).unwrap(), "vim");
+
+        std::fs::write(&local, "[core]\neditor = nano\n").unwrap();
+        assert_eq!(core_editor(&files).unwrap(), "nano");
+    }
+
+    #[test]
+    fn test_default_files() {
+        let env = env(&[
+            ("HOME", "/home/me"),
+            ("GIT_CONFIG_SYSTEM", "/opt/gitconfig"),
+        ]);
+        let files = default_files(&env);
+        assert_eq!(
+            files[..3],
+            [
+                PathBuf::from("/opt/gitconfig"),
+                PathBuf::from("/home/me/.config/git/config"),
+                PathBuf::from("/home/me/.gitconfig"),
+            ]
+        );
+
+        let mut env = env;
+        env.insert("GIT_CONFIG_NOSYSTEM".into(), "1".into());
+        env.insert("GIT_CONFIG_GLOBAL".into(), "/tmp/global".into());
+        assert_eq!(default_files(&env)[0], PathBuf::from("/tmp/global"));
+    }
+
+    #[test]
+    fn test_repository_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write("main/.git/config", "");
+        write("main/.git/worktrees/wt/commondir", "../..\n");
+        write(
+            "wt/.git",
+            &format!(
+                "gitdir: {}\n",
+                dir.path().join("main/.git/worktrees/wt").display()
+            ),
+        );
+        write("main/.git/modules/sub/config", "");
+        write("main/sub/.git", "gitdir: ../.git/modules/sub\n");
+        std::fs::create_dir_all(dir.path().join("main/sub/src")).unwrap();
+
+        let config = |path: &str| {
+            let config = repository_config(&dir.path().join(path)).unwrap();
+            config.canonicalize().unwrap()
+        };
+        let main = dir.path().join("main/.git/config").canonicalize().unwrap();
+        assert_eq!(config("main"), main);
+        assert_eq!(config("wt"), main);
+        assert_eq!(
+            config("main/sub/src"),
+            dir.path()
+                .join("main/.git/modules/sub/config")
+                .canonicalize()
+                .unwrap()
+        );
+    }
+}
@@ -1,7 +1,9 @@
 //! Generate commands for opening paths and URIs in the default system handler.
 //!
 //! These methods return [`std::process::Command`] instances that can immediately be run to open
-//! the given target, or modified to provide different stdin/stdout/stderr streams.
+//! the given target, or modified to provide different stdin/stdout/stderr streams. Each also has a
+//! `_spec` variant that returns a [`CommandSpec`], which can be inspected, compared, or printed
+//! before being converted into a [`Command`].
 //!
 //! This crate used <https://dwheeler.com/essays/open-files-urls.html> as a reference.
 
@@ -16,7 +18,6 @@
     absolute_paths_not_starting_with_crate,
     anonymous_parameters,
     bad_style,
-    const_err,
     dead_code,
     keyword_idents,
     improper_ctypes,
@@ -31,13 +32,10 @@
     overflowing_literals,
     path_statements,
     patterns_in_fns_without_body,
-    pointer_structural_match,
-    private_in_public,
     semicolon_in_expressions_from_macros,
     single_use_lifetimes,
     trivial_casts,
     trivial_numeric_casts,
-    unaligned_references,
     unconditional_recursion,
     unreachable_pub,
     unsafe_code,
@@ -53,26 +51,51 @@
     while_true
 )]
 
-use std::{path::PathBuf, process::Command};
+use std::{
+    path::PathBuf,
+    process::{Command, ExitStatus},
+};
 use thiserror::Error;
 
-mod path_or_uri;
-
-#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod browser;
+mod command_spec;
+mod desktop_entry;
+mod edit;
+mod editor;
+mod executable;
+mod expand;
+mod git_config;
 mod linux;
-#[cfg(target_os = "macos")]
 mod macos;
-#[cfg(target_os = "windows")]
+mod opener;
+mod path_or_uri;
+mod platform;
+mod policy;
+mod reveal;
+mod terminal;
+#[cfg(test)]
+mod test_util;
 mod windows;
+mod wsl;
+mod xdg_mime;
 
-#[cfg(not(any(target_os = "windows", target_os = "macos")))]
-use linux::open as sys_open;
-#[cfg(target_os = "macos")]
-use macos::open as sys_open;
-#[cfg(target_os = "windows")]
-use windows::open as sys_open;
+#[cfg(all(
+    feature = "portal",
+    not(any(target_os = "windows", target_os = "macos"))
+))]
+pub mod portal;
 
-pub use path_or_uri::PathOrURI;
+pub use command_spec::CommandSpec;
+pub use edit::{edit_text, EditOptions};
+#[cfg(feature = "serde")]
+pub use edit::{edit_value, ValueFormat};
+pub use editor::{EditorChain, EditorTemplate};
+pub use expand::{Environment, SystemEnvironment};
+pub use opener::{Opener, StdioMode};
+pub use path_or_uri::{ParseError, PathOrURI};
+pub use platform::Platform;
+pub use policy::OpenPolicy;
+pub use windows::WindowsStrategy;
 
 /// Type alias for the most common results in this crate.
 pub type Result<T = Command, E = Error> = std::result::Result<T, E>;
@@ -81,25 +104,40 @@ pub type Result<T = Command, E = Error> = std::result::Result<T, E>;
 pub const BROWSER_ENV: &str = "BROWSER";
 /// The environment variable checked when opening in a text editor.
 pub const EDITOR_ENV: &str = "EDITOR";
+/// The environment variable checked for the terminal emulator to run terminal editors in when no
+/// terminal is attached.
+pub const TERMINAL_ENV: &str = "TERMINAL";
+/// The environment variable checked before [`EDITOR_ENV`] when opening in a text editor, if a
+/// terminal is attached.
+pub const VISUAL_ENV: &str = "VISUAL";
 
 #[derive(Debug, Error)]
 /// Errors that may occur when generating a [`Command`].
+///
+/// Some variants only exist when the cargo feature that can cause them is enabled, so matches must
+/// include a wildcard arm.
+#[non_exhaustive]
 pub enum Error {
     /// Failed to convert a file path to a URI. This may be returned on Windows, where this is used
-    /// to ensure no odd behavior with confusing paths and CLi options (which start with a `/` on
+    /// to ensure no odd behavior with confusing paths and CLI options (which start with a `/` on
     /// Windows).
     ///
     /// See [`PathOrURI::url()`] for possible error cases.
     #[error("could not convert file path to URI: {0:?}")]
     FileToURI(PathBuf),
+    /// Failed to convert a file path to a path the Windows host can access. This may be returned
+    /// inside WSL when the path is not valid UTF-8, or when it is only accessible through the
+    /// distribution's network share and `WSL_DISTRO_NAME` is not set.
+    #[error("could not convert file path to a Windows path: {0:?}")]
+    WslPath(PathBuf),
+    /// The target path contains a NUL byte, which cannot be passed to a program as an argument.
+    #[error("target path contains a NUL byte: {0:?}")]
+    NulByte(PathBuf),
     /// An I/O error occurred while setting up the command.
     #[error("I/O error occurred: {0}")]
     IO(#[from] std::io::Error),
-    /// A required executable was not found. This will most likely always be `xdg-open` on Unix-y
-    /// systems, as Windows as macOS use built-in commands.
-    ///
-    /// If this error occurs with `xdg-open`, the user should install the `xdg-utils` for their
-    /// system.
+    /// A required executable was not found. On Windows and macOS, this is a built-in command. On
+    /// other systems, [`Error::NoOpener`] is returned instead when no opener exists.
     #[error("executable {exe} not found: {error}")]
     NotFound {
         /// The program that couldn't be found.
@@ -108,86 +146,484 @@ pub enum Error {
         /// The error returned by `which`.
         error: which::Error,
     },
+    /// The value of an environment variable could not be split into a program and its
+    /// arguments, most likely because of unbalanced quotes or a trailing backslash.
+    #[error("could not parse {var}={value:?} as a command: {error}")]
+    ParseCommand {
+        /// The environment variable that was read.
+        var: String,
+        /// The value of the environment variable.
+        value: String,
+        #[source]
+        /// The error returned while splitting the value into words.
+        error: shell_words::ParseError,
+    },
+    /// The application passed to [`open_with`] or [`Opener::app`] could not be split into a
+    /// program and its arguments, most likely because of unbalanced quotes or a trailing
+    /// backslash.
+    #[error("could not parse the application {app:?} as a command: {error}")]
+    ParseApp {
+        /// The application that was given.
+        app: String,
+        #[source]
+        /// The error returned while splitting the application into words.
+        error: shell_words::ParseError,
+    },
+    /// None of the known openers exist on this system. This is only returned on Unix-y systems
+    /// other than macOS, where there is no single built-in command.
+    ///
+    /// The user should most likely install the `xdg-utils` package for their system.
+    #[error("no program to open with was found (tried {})", .tried.join(", "))]
+    NoOpener {
+        /// The programs that were tried, in order.
+        tried: Vec<String>,
+    },
+    /// The command that was run to open the target exited unsuccessfully.
+    #[error("opening command failed: {0}")]
+    ExitStatus(ExitStatus),
+    /// An error occurred while talking to a service over D-Bus.
+    #[cfg(feature = "dbus")]
+    #[error("D-Bus error occurred: {0}")]
+    DBus(#[from] zbus::Error),
+    /// Opening in a web browser was explicitly disabled by setting [`BROWSER_ENV`] to an empty
+    /// string or `none`.
+    #[error("opening in a web browser was disabled by {BROWSER_ENV}")]
+    BrowserDisabled,
+    /// Waiting for the application to exit was requested, but cannot be done reliably for this
+    /// target. The message explains why, e.g. that the handler hands the target off to an already
+    /// running instance and exits immediately.
+    #[error("cannot wait for the application to exit: {0}")]
+    WaitUnsupported(String),
+    /// The terminal emulator that an editor was run in exited too soon for anything to have been
+    /// edited, likely because it handed the editor to an instance that was already running. Set
+    /// [`TERMINAL_ENV`] to an emulator that waits for the command it runs.
+    #[error("the terminal emulator {0} exited before the editor could be used")]
+    TerminalDetached(String),
+    /// The target was rejected by the [`OpenPolicy`], either because of its scheme or by the
+    /// confirmation function.
+    #[error("opening {0} is not allowed")]
+    Denied(String),
+    /// The target is a file that the system handler may run instead of opening, such as a script
+    /// or a desktop entry. See [`OpenPolicy::executables`].
+    #[error("refusing to open {path:?} because {reason}, so it may be run")]
+    Executable {
+        /// The path of the file.
+        path: PathBuf,
+        /// Why the file is considered executable.
+        reason: String,
+    },
+    /// More targets were given to open at once than the configured limit. See
+    /// [`Opener::max_targets`].
+    #[error("refusing to open {count} targets at once (the limit is {max})")]
+    TooManyTargets {
+        /// The number of targets given.
+        count: usize,
+        /// The most targets allowed at once.
+        max: usize,
+    },
+    /// A path to expand refers to an environment variable that is not set. See
+    /// [`PathOrURI::expand`].
+    #[error("cannot expand {path:?} because the environment variable {var} is not set")]
+    UndefinedVar {
+        /// The name of the variable.
+        var: String,
+        /// The path that was being expanded.
+        path: PathBuf,
+    },
+    /// A path to expand starts with `~`, but the home directory is unknown. See
+    /// [`PathOrURI::expand`].
+    #[error("cannot expand {path:?} because the home directory of {} is unknown", .user.as_deref().unwrap_or("the current user"))]
+    UnknownHome {
+        /// The user after the `~`, or `None` for the current user.
+        user: Option<String>,
+        /// The path that was being expanded.
+        path: PathBuf,
+    },
+    /// A value to edit with [`edit_value`] cannot be serialized in the requested format.
+    #[cfg(feature = "serde")]
+    #[error("could not serialize the value to edit as {format}: {error}")]
+    Serialize {
+        /// The format the value was serialized as.
+        format: ValueFormat,
+        /// The error message from the serializer.
+        error: String,
+    },
 }
 
+/// Like [`ensure_command_in`] with the current system, but only checks when generating commands
+/// for the current platform.
 #[inline]
-fn ensure_command(cmd: &str) -> Result<()> {
+fn ensure_command_on(platform: Platform, cmd: &str) -> Result<()> {
+    platform::PlatformOptions::default().ensure_command(platform, cmd)
+}
+
+/// Returns an error if `cmd` cannot be found in `env`.
+#[inline]
+fn ensure_command_in<E: Environment + ?Sized>(env: &E, cmd: &str) -> Result<()> {
     #[cfg(feature = "tracing")]
     tracing::trace!("checking if executable \"{}\" exists", cmd);
 
-    which::which(cmd)
-        .map(|_| ())
-        .map_err(|error| Error::NotFound {
-            exe: cmd.to_string(),
-            error,
-        })
+    env.which(cmd).map(|_| ()).map_err(|error| Error::NotFound {
+        exe: cmd.to_string(),
+        error,
+    })
 }
 
 #[inline]
-fn open_with_command(cmd: &str, target: &PathOrURI) -> Result {
-    ensure_command(cmd)?;
+fn open_with_command<S: AsRef<str>>(
+    options: platform::PlatformOptions<'_>,
+    platform: Platform,
+    cmd: &str,
+    args: &[S],
+    target: &PathOrURI,
+) -> Result<CommandSpec> {
+    options.ensure_command(platform, cmd)?;
 
     #[cfg(feature = "tracing")]
     tracing::debug!("opening {} with {}", target, cmd);
 
-    let mut cmd = Command::new(cmd);
-    cmd.arg(target.to_string());
-    Ok(cmd)
+    Ok(CommandSpec::new(cmd)
+        .args(args.iter().map(AsRef::as_ref))
+        .arg(target.to_arg()?))
 }
 
-#[inline]
-fn open_env(env: &str, target: &PathOrURI) -> Result {
-    #[cfg(feature = "tracing")]
-    tracing::trace!("checking if {} exists in environment", env);
-
-    if let Ok(cmd) = std::env::var(env) {
-        #[cfg(feature = "tracing")]
-        tracing::trace!("found {} = {}", env, cmd);
-        open_with_command(&cmd, target)
-    } else {
-        #[cfg(feature = "tracing")]
-        tracing::trace!("{} not found, using system default handler", env);
-        sys_open(target)
-    }
+/// Splits `value`, read from the environment variable `var`, into words the way a POSIX shell
+/// would.
+///
+/// The first word is returned as the program, the rest as the arguments to pass it before the
+/// target. Returns `Ok(None)` if `value` contains no words.
+fn split_command(var: &str, value: &str) -> Result<Option<(String, Vec<String>)>> {
+    split_words(value).map_err(|error| Error::ParseCommand {
+        var: var.to_string(),
+        value: value.to_string(),
+        error,
+    })
+}
+
+/// Like [`split_command`], but for an application given by the caller, such as the `app` of
+/// [`open_with`].
+fn split_app(app: &str) -> Result<Option<(String, Vec<String>)>> {
+    split_words(app).map_err(|error| Error::ParseApp {
+        app: app.to_string(),
+        error,
+    })
+}
+
+fn split_words(
+    value: &str,
+) -> std::result::Result<Option<(String, Vec<String>)>, shell_words::ParseError> {
+    let mut words = shell_words::split(value)?.into_iter();
+    Ok(words.next().map(|cmd| (cmd, words.collect())))
 }
 
 /// Open the target in the default system handler.
 ///
-/// This function ignores special environment variables that can tell CLI apps what to use. If you
-/// want to consider those variables, use [`open_browser`] or [`open_editor`].
+/// This is a shortcut for [`Opener::new`]. This function ignores special environment variables
+/// that can tell CLI apps what to use. If you want to consider those variables, use
+/// [`open_browser`] or [`open_editor`].
+///
+/// Only paths and `http`, `https`, and `mailto` URIs are opened. Use [`Opener::policy`] to allow
+/// other schemes.
 ///
 /// # Errors
 ///
-/// See [`Error`].
+/// - [`Error::Denied`] if the target is not allowed by the default [`OpenPolicy`].
+/// - See [`Error`] for other possible errors.
 pub fn open<T>(target: T) -> Result
 where
     PathOrURI: From<T>,
 {
-    sys_open(&PathOrURI::from(target))
+    Opener::new().command(target)
+}
+
+/// Like [`open`], but returns a [`CommandSpec`] instead of a [`Command`].
+///
+/// # Errors
+///
+/// See [`Error`].
+pub fn open_spec<T>(target: T) -> Result<CommandSpec>
+where
+    PathOrURI: From<T>,
+{
+    Opener::new().spec(target)
+}
+
+/// Open the target in the default handler of the given platform.
+///
+/// Unlike [`open`], this can generate commands for platforms other than the current one, e.g. to
+/// run on a remote machine. Executables are only checked for existence when `platform` is the
+/// current platform, and platform detection (such as for WSL) is skipped otherwise.
+///
+/// # Errors
+///
+/// See [`Error`].
+pub fn open_for<T>(platform: Platform, target: T) -> Result
+where
+    PathOrURI: From<T>,
+{
+    Opener::new().platform(platform).command(target)
+}
+
+/// Like [`open_for`], but returns a [`CommandSpec`] instead of a [`Command`].
+///
+/// # Errors
+///
+/// See [`Error`].
+pub fn open_for_spec<T>(platform: Platform, target: T) -> Result<CommandSpec>
+where
+    PathOrURI: From<T>,
+{
+    Opener::new().platform(platform).spec(target)
+}
+
+/// Open the target with a specific application.
+///
+/// How `app` is interpreted depends on the platform:
+///
+/// - **Linux**: a desktop file ID such as `org.kde.okular.desktop` (the `.desktop` suffix is
+///   optional), launched using the entry's `Exec` key like `gtk-launch` does. If no such entry
+///   exists, `app` is treated as an executable, which may include arguments, e.g.
+///   `firefox --new-window`.
+/// - **macOS**: an application name or path, passed to `open -a`, or a bundle identifier such as
+///   `org.mozilla.firefox`, passed to `open -b`.
+/// - **Windows**: an executable name or path, passed to `start`.
+///
+/// # Errors
+///
+/// - [`Error::NotFound`] if the application cannot be found on the current system.
+/// - [`Error::ParseApp`] if `app` is an executable with malformed quoting.
+/// - See [`Error`] for other possible errors.
+pub fn open_with<T>(app: &str, target: T) -> Result
+where
+    PathOrURI: From<T>,
+{
+    Opener::new().app(app).fallback(false).command(target)
+}
+
+/// Like [`open_with`], but returns a [`CommandSpec`] instead of a [`Command`].
+///
+/// # Errors
+///
+/// See [`open_with`].
+pub fn open_with_spec<T>(app: &str, target: T) -> Result<CommandSpec>
+where
+    PathOrURI: From<T>,
+{
+    Opener::new().app(app).fallback(false).spec(target)
+}
+
+/// Reveal the paths in the system file manager, with the files selected.
+///
+/// - **Linux**: with the `dbus` feature enabled, this asks the file manager to show the files
+///   through the `org.freedesktop.FileManager1` D-Bus interface. Without the feature, or if no file
+///   manager provides that interface, the parent directories are opened instead.
+/// - **macOS**: this runs `open -R`.
+/// - **Windows**: this runs `explorer /select,<path>` for each path.
+///
+/// Relative paths are resolved against the current directory. Like [`launch`], this does not wait
+/// for any commands it runs to exit.
+///
+/// # Errors
+///
+/// See [`Error`].
+pub fn reveal<I, P>(paths: I) -> Result<()>
+where
+    I: IntoIterator<Item = P>,
+    P: Into<PathBuf>,
+{
+    let paths: Vec<PathBuf> = paths.into_iter().map(Into::into).collect();
+    reveal::reveal(&paths)
+}
+
+/// Generate the commands that reveal the paths in the file manager of the given platform.
+///
+/// On Linux, these commands open the parent directories, since the D-Bus interface used by
+/// [`reveal`] cannot be called through a command.
+///
+/// # Errors
+///
+/// See [`Error`].
+pub fn reveal_specs<I, P>(platform: Platform, paths: I) -> Result<Vec<CommandSpec>>
+where
+    I: IntoIterator<Item = P>,
+    P: Into<PathBuf>,
+{
+    let paths: Vec<PathBuf> = paths.into_iter().map(Into::into).collect();
+    reveal::specs(platform, &paths)
+}
+
+/// Open the target in the default system handler immediately, instead of returning a command.
+///
+/// With the `portal` feature enabled, this uses `xdg-desktop-portal` when running inside a Flatpak
+/// or Snap sandbox. Otherwise, this runs the command returned by [`open`] without waiting for it
+/// to exit. Use [`Opener::launch`] for more control.
+///
+/// # Errors
+///
+/// See [`Error`].
+pub fn launch<T>(target: T) -> Result<()>
+where
+    PathOrURI: From<T>,
+{
+    Opener::new().launch(target)
+}
+
+/// Open all of the targets in their default system handlers.
+///
+/// Targets that share a handler which accepts several targets at once, such as `open` on macOS,
+/// are opened with a single command. See [`Opener::specs`] for details, and
+/// [`Opener::launch_many`] to run the commands with a limit on how many run at the same time.
+///
+/// # Errors
+///
+/// - [`Error::TooManyTargets`] if there are more than 32 targets. Use [`Opener::max_targets`] to
+///   change the limit.
+/// - See [`Error`] for other possible errors.
+pub fn open_many<I, T>(targets: I) -> Result<Vec<Command>>
+where
+    I: IntoIterator<Item = T>,
+    PathOrURI: From<T>,
+{
+    Opener::new().commands(targets)
+}
+
+/// Like [`open_many`], but returns [`CommandSpec`]s instead of [`Command`]s.
+///
+/// # Errors
+///
+/// See [`open_many`].
+pub fn open_many_specs<I, T>(targets: I) -> Result<Vec<CommandSpec>>
+where
+    I: IntoIterator<Item = T>,
+    PathOrURI: From<T>,
+{
+    Opener::new().specs(targets)
 }
 
 /// Open the target in the web browser specified by [`BROWSER_ENV`], or the system handler if not
 /// set.
 ///
+/// The variable may contain a list of candidate commands separated by `:` (`;` on Windows). Each
+/// candidate is split into words like a POSIX shell would, so it may contain arguments (e.g.
+/// `firefox --new-window`). A `%s` in a candidate is replaced with the target and `%%` with a
+/// literal `%`; if there is no `%s`, the target is passed as the last argument. The first
+/// candidate whose program exists is used. If none exist, the system handler is used instead.
+///
 /// # Errors
 ///
-/// See [`Error`].
+/// - [`Error::BrowserDisabled`] if the variable is empty or `none`.
+/// - [`Error::ParseCommand`] if a candidate has malformed quoting.
+/// - See [`Error`] for errors from the system handler.
 pub fn open_browser<T>(target: T) -> Result
 where
     PathOrURI: From<T>,
 {
-    open_env(BROWSER_ENV, &PathOrURI::from(target))
+    Opener::browser().command(target)
 }
 
-/// Open the target in the text editor specified by [`EDITOR_ENV`], or the system handler if not
-/// set.
+/// Like [`open_browser`], but returns a [`CommandSpec`] instead of a [`Command`].
 ///
 /// # Errors
 ///
-/// See [`Error`].
+/// See [`open_browser`].
+pub fn open_browser_spec<T>(target: T) -> Result<CommandSpec>
+where
+    PathOrURI: From<T>,
+{
+    Opener::browser().spec(target)
+}
+
+/// Open the target in the text editor found by the default [`EditorChain`]: [`VISUAL_ENV`] if a
+/// terminal is attached, [`EDITOR_ENV`], git's `core.editor`, and then common editors like
+/// `sensible-editor`, `vi`, and `nano`.
+///
+/// Commands from variables and `core.editor` are split into words like a POSIX shell would, so
+/// they may contain arguments (e.g. `code --wait`). These are passed to the program before the
+/// target. The system handler is never used, since it may run scripts instead of editing them.
+///
+/// # Errors
+///
+/// - [`Error::NoOpener`] if no editor is found.
+/// - [`Error::NotFound`] if the editor from a variable or `core.editor` does not exist.
+/// - See [`Error`] for other possible errors.
 pub fn open_editor<T>(target: T) -> Result
 where
     PathOrURI: From<T>,
 {
-    open_env(EDITOR_ENV, &PathOrURI::from(target))
+    Opener::editor().command(target)
+}
+
+/// Like [`open_editor`], but returns a [`CommandSpec`] instead of a [`Command`].
+///
+/// # Errors
+///
+/// See [`Error`].
+pub fn open_editor_spec<T>(target: T) -> Result<CommandSpec>
+where
+    PathOrURI: From<T>,
+{
+    Opener::editor().spec(target)
+}
+
+/// Open the file in the text editor found like [`open_editor`] does, at the given line and, if
+/// given, column.
+///
+/// If the editor is a known one, such as `vim`, `emacs`, `nano`, `code`, `subl`, `hx`, or
+/// `idea`, it is passed the position in its own syntax, e.g. `vim +42 file` or
+/// `code -g file:42:7`. Other editors are passed the file without a position. Use
+/// [`Opener::editor_template`] to teach the opener about other editors.
+///
+/// # Errors
+///
+/// See [`open_editor`].
+pub fn open_editor_at<T>(target: T, line: usize, column: Option<usize>) -> Result
+where
+    PathOrURI: From<T>,
+{
+    Opener::editor_at(line, column).command(target)
+}
+
+/// Like [`open_editor_at`], but returns a [`CommandSpec`] instead of a [`Command`].
+///
+/// # Errors
+///
+/// See [`Error`].
+pub fn open_editor_at_spec<T>(target: T, line: usize, column: Option<usize>) -> Result<CommandSpec>
+where
+    PathOrURI: From<T>,
+{
+    Opener::editor_at(line, column).spec(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command(EDITOR_ENV, "vim").unwrap(),
+            Some(("vim".into(), vec![]))
+        );
+        assert_eq!(
+            split_command(EDITOR_ENV, "code --wait").unwrap(),
+            Some(("code".into(), vec!["--wait".into()]))
+        );
+        assert_eq!(
+            split_command(EDITOR_ENV, r#"'/opt/My Editor/bin/edit' -a "b c" d\ e"#).unwrap(),
+            Some((
+                "/opt/My Editor/bin/edit".into(),
+                vec!["-a".into(), "b c".into(), "d e".into()]
+            ))
+        );
+        assert_eq!(split_command(EDITOR_ENV, "  ").unwrap(), None);
+    }
+
+    #[test]
+    fn test_split_command_malformed() {
+        assert!(matches!(
+            split_command(BROWSER_ENV, "firefox 'unterminated"),
+            Err(Error::ParseCommand { var, .. }) if var == BROWSER_ENV
+        ));
+    }
 }
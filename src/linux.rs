@@ -1,7 +1,255 @@
-use crate::PathOrURI;
+use crate::{
+    desktop_entry::{self, DesktopEntry},
+    platform::PlatformOptions,
+    xdg_mime, CommandSpec, Error, PathOrURI, Platform, SystemEnvironment,
+};
 
-const OPEN_COMMAND: &str = "xdg-open";
+/// Launches desktop entries when generating commands for another system.
+const DESKTOP_LAUNCHER: &str = "gtk-launch";
 
-pub(crate) fn open(target: &PathOrURI) -> crate::Result {
-    crate::open_with_command(OPEN_COMMAND, target)
+/// Known openers, in order of preference, along with the arguments each needs before the target.
+const OPENERS: &[(&str, &[&str])] = &[
+    ("xdg-open", &[]),
+    ("gio", &["open", "--"]),
+    ("kde-open", &[]),
+    ("kde-open5", &[]),
+    ("exo-open", &[]),
+    ("gnome-open", &[]),
+    ("wslview", &[]),
+    ("sensible-browser", &[]),
+];
+
+/// Programs that hand the target off to an already running instance, if there is one, and exit
+/// immediately. Waiting for these would not wait for the target to be closed.
+const FORWARDERS: &[&str] = &[
+    "firefox",
+    "firefox-esr",
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+    "brave-browser",
+    "code",
+    "codium",
+    "subl",
+    "nautilus",
+    "nemo",
+    "thunar",
+    "dolphin",
+    "soffice",
+    "libreoffice",
+];
+
+pub(crate) fn open(target: &PathOrURI, options: PlatformOptions<'_>) -> crate::Result<CommandSpec> {
+    if let Some(env) = options.env(Platform::Linux).filter(|env| env.is_wsl()) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!("running inside WSL, opening through the Windows host");
+        return crate::wsl::open(target, options, env);
+    }
+
+    if options.wait {
+        return open_wait(target, options);
+    }
+
+    for (cmd, args) in OPENERS {
+        match crate::open_with_command(options, Platform::Linux, cmd, args, target) {
+            Err(Error::NotFound { .. }) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!("{} not found, trying next opener", cmd);
+            }
+            result => return result,
+        }
+    }
+
+    Err(Error::NoOpener {
+        tried: OPENERS.iter().map(|(cmd, _)| (*cmd).to_string()).collect(),
+    })
+}
+
+/// Generate a command that runs the default application for the target directly, so that the
+/// command exits when the application does.
+///
+/// The generic openers exit as soon as they have started the application, so the default is looked
+/// up from the `mimeapps.list` files and desktop entries instead.
+fn open_wait(target: &PathOrURI, options: PlatformOptions<'_>) -> crate::Result<CommandSpec> {
+    if !Platform::Linux.is_current() {
+        return Err(Error::WaitUnsupported(
+            "the default application on another Linux system cannot be determined".to_string(),
+        ));
+    }
+
+    let mime = xdg_mime::mime_type(target).ok_or_else(|| {
+        Error::WaitUnsupported(format!("could not determine the MIME type of {target}"))
+    })?;
+    let entry = xdg_mime::default_entry(
+        &mime,
+        &xdg_mime::config_dirs(),
+        &desktop_entry::data_dirs(&SystemEnvironment),
+        &xdg_mime::current_desktops(),
+    )
+    .ok_or_else(|| Error::WaitUnsupported(format!("no default application found for {mime}")))?;
+
+    ensure_waitable(&entry)?;
+    entry_command(&mime, &entry, target, options)
+}
+
+/// Ensures that running the desktop entry blocks until the target is closed.
+fn ensure_waitable(entry: &DesktopEntry) -> crate::Result<()> {
+    let name = entry.name.as_deref().unwrap_or("the default application");
+    if entry.dbus_activatable {
+        return Err(Error::WaitUnsupported(format!(
+            "{name} is launched over D-Bus"
+        )));
+    }
+
+    let program = entry.program().unwrap_or_default();
+    let file_name = std::path::Path::new(&program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    if FORWARDERS.contains(&file_name) {
+        return Err(Error::WaitUnsupported(format!(
+            "{name} may pass the target to an instance that is already running"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Generate a command that opens the target with the given application.
+///
+/// On the current system, or the system described by the options' environment, `app` is first
+/// looked up as a desktop file ID and launched using the entry's `Exec` key, like `gtk-launch`
+/// does. Otherwise, it is treated as an executable, which may include arguments. When generating
+/// commands for another system, IDs ending in `.desktop` are passed to `gtk-launch` instead.
+///
+/// When waiting, the application is always run directly, since `gtk-launch` exits as soon as the
+/// application has started.
+pub(crate) fn open_with(
+    app: &str,
+    target: &PathOrURI,
+    options: PlatformOptions<'_>,
+) -> crate::Result<CommandSpec> {
+    if let Some(env) = options.env(Platform::Linux) {
+        if let Some(entry) = DesktopEntry::find(app, &desktop_entry::data_dirs(env)) {
+            if options.wait {
+                ensure_waitable(&entry)?;
+            }
+            return entry_command(app, &entry, target, options);
+        }
+    } else if !options.wait
+        && std::path::Path::new(app)
+            .extension()
+            .is_some_and(|ext| ext == "desktop")
+    {
+        return Ok(CommandSpec::new(DESKTOP_LAUNCHER).args([app.to_string(), target.to_arg()?]));
+    }
+
+    match crate::split_app(app)? {
+        Some((cmd, args)) => {
+            crate::open_with_command(options, Platform::Linux, &cmd, &args, target)
+        }
+        None => Err(not_found(app)),
+    }
+}
+
+/// Returns the command that [`open_with`] generated as `spec` would be without any targets, if the
+/// application accepts several.
+pub(crate) fn open_with_template(app: &str, spec: &CommandSpec) -> Option<CommandSpec> {
+    if Platform::Linux.is_current() {
+        if let Some(entry) = DesktopEntry::find(app, &desktop_entry::data_dirs(&SystemEnvironment))
+        {
+            if !entry.accepts_multiple() {
+                return None;
+            }
+            let line = entry.command_line(&[])?;
+            let (cmd, args) = line.split_first()?;
+            return Some(CommandSpec::new(cmd.clone()).args(args.to_vec()));
+        }
+    }
+
+    (spec.program != DESKTOP_LAUNCHER).then(|| spec.without_last_arg())
+}
+
+/// Generate a command that launches a desktop entry with the target.
+fn entry_command(
+    app: &str,
+    entry: &DesktopEntry,
+    target: &PathOrURI,
+    options: PlatformOptions<'_>,
+) -> crate::Result<CommandSpec> {
+    #[cfg(feature = "tracing")]
+    tracing::debug!("opening {} with desktop entry {:?}", target, entry.path);
+
+    let line = entry
+        .command_line(&[target.to_arg()?])
+        .ok_or_else(|| not_found(app))?;
+    let (cmd, args) = line.split_first().ok_or_else(|| not_found(app))?;
+    options.ensure_command(Platform::Linux, cmd)?;
+    Ok(CommandSpec::new(cmd.clone()).args(args.to_vec()))
+}
+
+fn not_found(app: &str) -> Error {
+    Error::NotFound {
+        exe: app.to_string(),
+        error: which::Error::CannotFindBinaryPath,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(contents: &str) -> DesktopEntry {
+        DesktopEntry::parse(std::path::Path::new("test.desktop"), contents)
+    }
+
+    #[test]
+    fn test_entry_command_option_injection() {
+        let target = PathOrURI::from(std::path::PathBuf::from("--help"));
+        let spec = entry_command(
+            "cat",
+            &entry("[Desktop Entry]\nExec=cat %F\n"),
+            &target,
+            PlatformOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(spec, CommandSpec::new("cat").arg("./--help"));
+    }
+
+    #[test]
+    fn test_open_with_malformed_app() {
+        let env = crate::test_util::FakeSystem::default();
+        let options = PlatformOptions {
+            env: Some(&env),
+            ..PlatformOptions::default()
+        };
+        let target = PathOrURI::from(std::path::PathBuf::from("/tmp/a.pdf"));
+        let error = open_with("firefox 'x", &target, options).unwrap_err();
+        assert!(matches!(&error, Error::ParseApp { app, .. } if app == "firefox 'x"));
+        assert!(
+            error
+                .to_string()
+                .starts_with(r#"could not parse the application "firefox 'x" as a command"#),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_ensure_waitable() {
+        let okular = "[Desktop Entry]\nName=Okular\nExec=okular %U\n";
+        assert!(ensure_waitable(&entry(okular)).is_ok());
+
+        let firefox = "[Desktop Entry]\nName=Firefox\nExec=/usr/lib/firefox/firefox %u\n";
+        assert!(matches!(
+            ensure_waitable(&entry(firefox)),
+            Err(Error::WaitUnsupported(_))
+        ));
+
+        let activatable = format!("{okular}DBusActivatable=true\n");
+        assert!(matches!(
+            ensure_waitable(&entry(&activatable)),
+            Err(Error::WaitUnsupported(_))
+        ));
+    }
 }
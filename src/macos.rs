@@ -1,7 +1,58 @@
-use crate::PathOrURI;
+use std::path::Path;
+
+use crate::{platform::PlatformOptions, CommandSpec, PathOrURI, Platform};
 
 const OPEN_COMMAND: &str = "open";
 
-pub(crate) fn open(target: &PathOrURI) -> crate::Result {
-    crate::open_with_command(OPEN_COMMAND, target)
+/// The flag that makes `open` wait for the application to exit, if requested.
+fn wait_flag(options: PlatformOptions<'_>) -> Option<&'static str> {
+    options.wait.then_some("-W")
+}
+
+pub(crate) fn open(target: &PathOrURI, options: PlatformOptions<'_>) -> crate::Result<CommandSpec> {
+    let args: Vec<&str> = wait_flag(options).into_iter().collect();
+    crate::open_with_command(options, Platform::MacOS, OPEN_COMMAND, &args, target)
+}
+
+/// Returns whether `app` looks like a bundle identifier (e.g. `com.apple.Safari`) rather than an
+/// application name or path.
+fn is_bundle_id(app: &str) -> bool {
+    !Path::new(app)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("app"))
+        && app.split('.').count() >= 3
+        && app.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Generate a command that opens the target with the given application name, path, or bundle
+/// identifier.
+pub(crate) fn open_with(
+    app: &str,
+    target: &PathOrURI,
+    options: PlatformOptions<'_>,
+) -> crate::Result<CommandSpec> {
+    let flag = if is_bundle_id(app) { "-b" } else { "-a" };
+    let args: Vec<&str> = wait_flag(options).into_iter().chain([flag, app]).collect();
+    crate::open_with_command(options, Platform::MacOS, OPEN_COMMAND, &args, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_bundle_id() {
+        assert!(is_bundle_id("com.apple.Safari"));
+        assert!(is_bundle_id("org.mozilla.firefox"));
+        assert!(!is_bundle_id("Safari"));
+        assert!(!is_bundle_id("Visual Studio Code"));
+        assert!(!is_bundle_id("Foo.Bar.app"));
+        assert!(!is_bundle_id("/Applications/Utilities/Terminal.app"));
+        assert!(!is_bundle_id("My App 2.0.1"));
+    }
 }
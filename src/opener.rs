@@ -0,0 +1,1201 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    editor::Position, platform::PlatformOptions, CommandSpec, EditorChain, EditorTemplate, Error,
+    OpenPolicy, PathOrURI, Platform, Result, SystemEnvironment, WindowsStrategy, BROWSER_ENV,
+};
+
+/// How the standard input, output, and error streams of a launched command are set up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StdioMode {
+    /// Inherit the streams of the current process.
+    #[default]
+    Inherit,
+    /// Connect all streams to the null device.
+    Null,
+}
+
+impl StdioMode {
+    fn stdio(self) -> Stdio {
+        match self {
+            Self::Inherit => Stdio::inherit(),
+            Self::Null => Stdio::null(),
+        }
+    }
+}
+
+/// An environment variable that may name the program to open targets with.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum EnvVar {
+    /// The value is a single command, split into words like a POSIX shell would.
+    Command(String),
+    /// The value follows the `$BROWSER` convention. See [`open_browser`](crate::open_browser).
+    BrowserList(String),
+}
+
+/// The default for [`Opener::max_targets`].
+const DEFAULT_MAX_TARGETS: usize = 32;
+/// The default for [`Opener::max_concurrent`].
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+/// How often [`Opener::launch_many`] checks whether the commands it started have exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How long [`Opener::launch_many`] waits for a command to exit when not waiting for the
+/// applications, before assuming that it is the application itself instead of a launcher such as
+/// `xdg-open`.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A command generated for a single target.
+struct Resolved {
+    spec: CommandSpec,
+    /// The command without any targets, if the handler also accepts several targets at once.
+    template: Option<CommandSpec>,
+}
+
+impl Resolved {
+    fn single(spec: CommandSpec) -> Self {
+        Self {
+            spec,
+            template: None,
+        }
+    }
+
+    fn appended(spec: CommandSpec) -> Self {
+        let template = Some(spec.without_last_arg());
+        Self { spec, template }
+    }
+
+    /// Turns the command into a batch that other targets for the same handler can be added to.
+    fn into_batch(self) -> Batch {
+        let position = self.template.as_ref().and_then(|template| {
+            let count = self.spec.args.len().checked_sub(template.args.len())?;
+            let index = template
+                .args
+                .iter()
+                .zip(&self.spec.args)
+                .position(|(a, b)| a != b)
+                .unwrap_or(template.args.len());
+
+            let mut expected = template.clone();
+            let args = self.spec.args[index..index + count].iter().cloned();
+            expected.args.splice(index..index, args);
+            (count > 0 && expected == self.spec).then_some(index)
+        });
+
+        Batch {
+            template: self.template.zip(position),
+            spec: self.spec,
+        }
+    }
+}
+
+/// A command that may open several targets.
+struct Batch {
+    /// The command without any targets and the index to insert them at, if the handler accepts
+    /// several targets.
+    template: Option<(CommandSpec, usize)>,
+    spec: CommandSpec,
+}
+
+impl Batch {
+    /// Adds the targets of `other` to this batch, if both use the same handler and it accepts
+    /// several targets.
+    fn merge(&mut self, other: &Self) -> bool {
+        let (Some((template, index)), Some(other_template)) = (&self.template, &other.template)
+        else {
+            return false;
+        };
+        if (template, index) != (&other_template.0, &other_template.1) {
+            return false;
+        }
+
+        let count = other.spec.args.len() - template.args.len();
+        let args = other.spec.args[*index..index + count].iter().cloned();
+        let end = self.spec.args.len() - (template.args.len() - index);
+        self.spec.args.splice(end..end, args);
+        true
+    }
+}
+
+/// A builder for opening targets with configurable handlers and launch options.
+///
+/// When generating a command, the handler is chosen in the following order:
+///
+/// 1. The application set with [`Opener::app`], if any.
+/// 2. The programs named by the environment variables added with [`Opener::env`] and
+///    [`Opener::browser_env`], in the order they were added. Unset variables are skipped.
+/// 3. The text editor found by the [`EditorChain`] set with [`Opener::editor_chain`], if any.
+/// 4. The default handler of the target platform.
+///
+/// If a program chosen in steps 1 to 3 does not exist, [`Opener::fallback`] decides whether to
+/// move on to the next step or fail with [`Error::NotFound`].
+///
+/// [`open`](crate::open), [`open_browser`](crate::open_browser), and
+/// [`open_editor`](crate::open_editor) are shortcuts for [`Opener::new`], [`Opener::browser`],
+/// and [`Opener::editor`], respectively.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Opener {
+    platform: Platform,
+    env: Vec<EnvVar>,
+    app: Option<String>,
+    fallback: bool,
+    wait: bool,
+    stdio: Option<StdioMode>,
+    current_dir: Option<PathBuf>,
+    max_targets: usize,
+    max_concurrent: usize,
+    windows_strategy: WindowsStrategy,
+    policy: OpenPolicy,
+    expand: bool,
+    position: Option<Position>,
+    editor_templates: Vec<(String, EditorTemplate)>,
+    editor_chain: Option<EditorChain>,
+    #[cfg(all(
+        feature = "portal",
+        not(any(target_os = "windows", target_os = "macos"))
+    ))]
+    portal_options: crate::portal::PortalOptions,
+}
+
+impl Default for Opener {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Opener {
+    /// Create an opener that uses the default handler of the current platform.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            platform: Platform::current(),
+            env: Vec::new(),
+            app: None,
+            fallback: true,
+            wait: false,
+            stdio: None,
+            current_dir: None,
+            max_targets: DEFAULT_MAX_TARGETS,
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            windows_strategy: WindowsStrategy::default(),
+            policy: OpenPolicy::default(),
+            expand: false,
+            position: None,
+            editor_templates: Vec::new(),
+            editor_chain: None,
+            #[cfg(all(
+                feature = "portal",
+                not(any(target_os = "windows", target_os = "macos"))
+            ))]
+            portal_options: crate::portal::PortalOptions::default(),
+        }
+    }
+
+    /// Create an opener that uses the web browser specified by [`BROWSER_ENV`], falling back to
+    /// the default handler.
+    #[must_use]
+    pub fn browser() -> Self {
+        Self::new().browser_env(BROWSER_ENV)
+    }
+
+    /// Create an opener that uses the text editor found by the default [`EditorChain`]. If the
+    /// editor does not exist or none is found, this fails instead of falling back to the default
+    /// handler.
+    #[must_use]
+    pub fn editor() -> Self {
+        Self::new()
+            .editor_chain(EditorChain::default())
+            .fallback(false)
+    }
+
+    /// Like [`Opener::editor`], but opens targets at the given position. See
+    /// [`open_editor_at`](crate::open_editor_at).
+    #[must_use]
+    pub fn editor_at(line: usize, column: Option<usize>) -> Self {
+        Self::editor().position(line, column)
+    }
+
+    /// Generate commands for the given platform instead of the current one.
+    ///
+    /// Executables are only checked for existence when `platform` is the current platform, and
+    /// platform detection (such as for WSL) is skipped otherwise.
+    #[must_use]
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Consult the environment variable `var` for the program to open targets with.
+    ///
+    /// The value is split into words like a POSIX shell would, so it may contain arguments. These
+    /// are passed to the program before the target.
+    #[must_use]
+    pub fn env<S: Into<String>>(mut self, var: S) -> Self {
+        self.env.push(EnvVar::Command(var.into()));
+        self
+    }
+
+    /// Consult the environment variable `var`, which follows the `$BROWSER` convention, for the
+    /// program to open targets with. See [`open_browser`](crate::open_browser) for details.
+    #[must_use]
+    pub fn browser_env<S: Into<String>>(mut self, var: S) -> Self {
+        self.env.push(EnvVar::BrowserList(var.into()));
+        self
+    }
+
+    /// Open targets with the given application instead of the one from the environment or the
+    /// system. See [`open_with`](crate::open_with) for how `app` is interpreted on each platform.
+    #[must_use]
+    pub fn app<S: Into<String>>(mut self, app: S) -> Self {
+        self.app = Some(app.into());
+        self
+    }
+
+    /// Whether to move on to the next handler if the application or a program from the
+    /// environment does not exist. Defaults to `true`.
+    #[must_use]
+    pub fn fallback(mut self, fallback: bool) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Whether to wait for the application to exit. Defaults to `false`.
+    ///
+    /// When set, generated commands for the system handler only exit once the application does,
+    /// and [`Opener::launch`] blocks until then:
+    ///
+    /// - **Linux**: the default application is looked up and run directly, since `xdg-open` and
+    ///   similar openers exit as soon as the application starts. Applications that are launched
+    ///   over D-Bus or that pass the target to an already running instance (like most web
+    ///   browsers) cannot be waited for.
+    /// - **macOS**: `open -W` is used.
+    /// - **Windows**: `start /wait` is used.
+    ///
+    /// Programs from [`Opener::app`] and the environment are run as configured, so they must block
+    /// themselves, e.g. `code --wait`. If waiting is not possible, generating the command fails
+    /// with [`Error::WaitUnsupported`] rather than silently returning early.
+    #[must_use]
+    pub fn wait(mut self, wait: bool) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// How to set up the standard streams of generated commands.
+    ///
+    /// By default, the streams are left as [`Command`] sets them up: inherited when spawned, and
+    /// captured by [`Command::output`].
+    #[must_use]
+    pub fn stdio(mut self, stdio: StdioMode) -> Self {
+        self.stdio = Some(stdio);
+        self
+    }
+
+    /// The working directory to run generated commands in. Relative paths are resolved against it
+    /// before generating commands, so that they refer to the same files as they would in a shell
+    /// started there.
+    #[must_use]
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// The most targets [`Opener::specs`], [`Opener::commands`], and [`Opener::launch_many`]
+    /// accept at once, as a safety net against opening hundreds of windows by mistake. Defaults to
+    /// 32.
+    #[must_use]
+    pub fn max_targets(mut self, max: usize) -> Self {
+        self.max_targets = max;
+        self
+    }
+
+    /// The most commands [`Opener::launch_many`] runs at the same time. Defaults to 4. A value of
+    /// 0 is treated as 1.
+    #[must_use]
+    pub fn max_concurrent(mut self, max: usize) -> Self {
+        self.max_concurrent = max;
+        self
+    }
+
+    /// How to open targets on Windows. Defaults to [`WindowsStrategy::Start`].
+    ///
+    /// GUI applications may prefer [`WindowsStrategy::Rundll32`] or [`WindowsStrategy::Explorer`],
+    /// which do not flash a console window.
+    #[must_use]
+    pub fn windows_strategy(mut self, strategy: WindowsStrategy) -> Self {
+        self.windows_strategy = strategy;
+        self
+    }
+
+    /// Which targets may be opened. Defaults to [`OpenPolicy::default`], which only allows paths
+    /// and the `http`, `https`, and `mailto` schemes.
+    ///
+    /// The policy is checked before any command is generated, and rejected targets fail with
+    /// [`Error::Denied`].
+    #[must_use]
+    pub fn policy(mut self, policy: OpenPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Whether to expand `~` and environment variables in path targets before opening them, as
+    /// described in [`PathOrURI::expand`]. Defaults to `false`.
+    ///
+    /// Expansion happens before the policy is checked, so the policy sees the expanded path.
+    #[must_use]
+    pub fn expand(mut self, expand: bool) -> Self {
+        self.expand = expand;
+        self
+    }
+
+    /// Open targets at the given line and, if given, column, when the program from
+    /// [`Opener::env`] or [`Opener::editor_chain`] is a known text editor or has a template from
+    /// [`Opener::editor_template`]. Other programs are passed the target without a position.
+    #[must_use]
+    pub fn position(mut self, line: usize, column: Option<usize>) -> Self {
+        self.position = Some(Position { line, column });
+        self
+    }
+
+    /// Use `template` to pass the position set with [`Opener::position`] to the program named
+    /// `program`, instead of the built-in template, if any.
+    ///
+    /// `program` is matched against the file name of the program without an extension, so
+    /// `my-editor` matches both `/usr/bin/my-editor` and `C:\Tools\my-editor.exe`.
+    #[must_use]
+    pub fn editor_template<S: Into<String>>(
+        mut self,
+        program: S,
+        template: EditorTemplate,
+    ) -> Self {
+        self.editor_templates.push((program.into(), template));
+        self
+    }
+
+    /// Look for a text editor to open targets with using `chain`, after the variables added with
+    /// [`Opener::env`] and before the default handler.
+    ///
+    /// If no editor is found and [`Opener::fallback`] is unset, this fails with
+    /// [`Error::NoOpener`] instead of using the default handler, which may run scripts rather than
+    /// edit them.
+    #[must_use]
+    pub fn editor_chain(mut self, chain: EditorChain) -> Self {
+        self.editor_chain = Some(chain);
+        self
+    }
+
+    /// The options passed to `xdg-desktop-portal` when [`Opener::launch`] and
+    /// [`Opener::launch_many`] open targets through it. Defaults to
+    /// [`PortalOptions::default`](crate::portal::PortalOptions::default).
+    #[cfg(all(
+        feature = "portal",
+        not(any(target_os = "windows", target_os = "macos"))
+    ))]
+    #[must_use]
+    pub fn portal_options(mut self, options: crate::portal::PortalOptions) -> Self {
+        self.portal_options = options;
+        self
+    }
+
+    /// Returns the template for passing a position to `program`, if any.
+    fn editor_template_for(&self, program: &str) -> Option<EditorTemplate> {
+        let name = crate::editor::program_name(program);
+        self.editor_templates
+            .iter()
+            .rev()
+            .find(|(other, _)| other.eq_ignore_ascii_case(&name))
+            .map(|(_, template)| template.clone())
+            .or_else(|| crate::editor::known(program))
+    }
+
+    /// Handles a missing program according to [`Opener::fallback`].
+    fn missing(&self, result: Result<CommandSpec>) -> Result<Option<CommandSpec>> {
+        match result {
+            Err(Error::NotFound { .. } | Error::NoOpener { .. }) if self.fallback => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!("handler not found, falling back: {}", result.unwrap_err());
+                Ok(None)
+            }
+            result => result.map(Some),
+        }
+    }
+
+    fn env_spec(&self, var: &EnvVar, target: &PathOrURI) -> Result<Option<Resolved>> {
+        let (EnvVar::Command(name) | EnvVar::BrowserList(name)) = var;
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!("checking if {} exists in environment", name);
+
+        let Ok(value) = std::env::var(name) else {
+            return Ok(None);
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!("found {} = {}", name, value);
+
+        match var {
+            EnvVar::Command(_) => match crate::split_command(name, &value)? {
+                Some((cmd, args)) => self.program_spec(&cmd, &args, target),
+                None => Ok(None),
+            },
+            EnvVar::BrowserList(_) => Ok(self
+                .missing(crate::browser::command(self.platform, name, &value, target))?
+                .map(Resolved::single)),
+        }
+    }
+
+    /// Generate a command that opens the target with `cmd` and its `args`, at the position set
+    /// with [`Opener::position`] if the program supports it.
+    fn program_spec(
+        &self,
+        cmd: &str,
+        args: &[String],
+        target: &PathOrURI,
+    ) -> Result<Option<Resolved>> {
+        let positioned = self
+            .position
+            .and_then(|position| Some((position, self.editor_template_for(cmd)?)));
+        if let Some((position, template)) = positioned {
+            return Ok(self
+                .missing(crate::editor::command(
+                    self.platform,
+                    cmd,
+                    args,
+                    &template,
+                    position,
+                    target,
+                ))?
+                .map(Resolved::single));
+        }
+
+        Ok(self
+            .missing(crate::open_with_command(
+                self.platform_options(),
+                self.platform,
+                cmd,
+                args,
+                target,
+            ))?
+            .map(Resolved::appended))
+    }
+
+    fn chain_spec(&self, chain: &EditorChain, target: &PathOrURI) -> Result<Option<Resolved>> {
+        let Some(editor) = chain.resolve_with(&SystemEnvironment)? else {
+            if self.fallback {
+                return Ok(None);
+            }
+            return Err(Error::NoOpener {
+                tried: chain.tried(),
+            });
+        };
+        let Some(mut resolved) = self.program_spec(&editor.program, &editor.args, target)? else {
+            return Ok(None);
+        };
+
+        if self.platform == Platform::Linux && self.platform.is_current() {
+            let spec = chain.wrap_with(resolved.spec.clone(), &SystemEnvironment)?;
+            if spec != resolved.spec {
+                resolved = Resolved::single(spec);
+            }
+        }
+        Ok(Some(resolved))
+    }
+
+    fn resolve(&self, target: &PathOrURI) -> Result<Resolved> {
+        let options = self.platform_options();
+        if let Some(app) = &self.app {
+            if let Some(spec) = self.missing(self.platform.open_with(app, target, options))? {
+                let template = self.platform.batch_template(Some(app), &spec);
+                return Ok(Resolved { spec, template });
+            }
+        }
+
+        for var in &self.env {
+            if let Some(resolved) = self.env_spec(var, target)? {
+                return Ok(resolved);
+            }
+        }
+
+        if let Some(chain) = &self.editor_chain {
+            if let Some(resolved) = self.chain_spec(chain, target)? {
+                return Ok(resolved);
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!("using system default handler");
+
+        self.policy
+            .check_executable(target, self.platform.is_current())?;
+        let spec = self.platform.open(target, options)?;
+        let template = self.platform.batch_template(None, &spec);
+        Ok(Resolved { spec, template })
+    }
+
+    fn platform_options(&self) -> PlatformOptions<'static> {
+        PlatformOptions {
+            wait: self.wait,
+            windows: self.windows_strategy,
+            env: None,
+        }
+    }
+
+    /// Generate a [`CommandSpec`] that opens the target.
+    ///
+    /// # Errors
+    ///
+    /// See [`Error`].
+    pub fn spec<T>(&self, target: T) -> Result<CommandSpec>
+    where
+        PathOrURI: From<T>,
+    {
+        let target = self.prepare(PathOrURI::from(target))?;
+        self.build(&target)
+    }
+
+    /// Expands the target if [`Opener::expand`] is set and resolves relative paths against
+    /// [`Opener::current_dir`], then ensures it can be opened and the policy allows it.
+    fn prepare(&self, target: PathOrURI) -> Result<PathOrURI> {
+        let target = if self.expand {
+            target.expand()?
+        } else {
+            target
+        };
+        let target = match (&self.current_dir, target) {
+            (Some(dir), PathOrURI::Path(path)) if path.is_relative() => {
+                PathOrURI::Path(dir.join(path))
+            }
+            (_, target) => target,
+        };
+        target.validate()?;
+        self.policy.check(&target)?;
+        Ok(target)
+    }
+
+    /// Generate the spec for a target that was already checked.
+    fn build(&self, target: &PathOrURI) -> Result<CommandSpec> {
+        Ok(self.finish(self.resolve(target)?.spec))
+    }
+
+    /// Applies options that do not depend on the target to a generated spec.
+    fn finish(&self, mut spec: CommandSpec) -> CommandSpec {
+        if let Some(dir) = &self.current_dir {
+            spec.current_dir = Some(dir.clone());
+        }
+        spec
+    }
+
+    fn collect_targets<I, T>(&self, targets: I) -> Result<Vec<PathOrURI>>
+    where
+        I: IntoIterator<Item = T>,
+        PathOrURI: From<T>,
+    {
+        let targets: Vec<PathOrURI> = targets.into_iter().map(PathOrURI::from).collect();
+        if targets.len() > self.max_targets {
+            return Err(Error::TooManyTargets {
+                count: targets.len(),
+                max: self.max_targets,
+            });
+        }
+        targets
+            .into_iter()
+            .map(|target| self.prepare(target))
+            .collect()
+    }
+
+    /// Generate the [`CommandSpec`]s that open all of the targets.
+    ///
+    /// Targets that share a handler which accepts several targets at once are opened with a single
+    /// command, e.g. `open a b` on macOS, `code a b` from [`EDITOR_ENV`], or a desktop entry whose
+    /// `Exec` key uses `%F` or `%U`. Otherwise, each target gets its own command. Commands are
+    /// returned in the order their first target was given.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::TooManyTargets`] if there are more targets than [`Opener::max_targets`].
+    /// - See [`Error`] for errors while generating the commands.
+    pub fn specs<I, T>(&self, targets: I) -> Result<Vec<CommandSpec>>
+    where
+        I: IntoIterator<Item = T>,
+        PathOrURI: From<T>,
+    {
+        let mut batches: Vec<Batch> = Vec::new();
+        for target in self.collect_targets(targets)? {
+            let batch = self.resolve(&target)?.into_batch();
+            if !batches.iter_mut().any(|other| other.merge(&batch)) {
+                batches.push(batch);
+            }
+        }
+
+        Ok(batches
+            .into_iter()
+            .map(|batch| self.finish(batch.spec))
+            .collect())
+    }
+
+    /// Like [`Opener::specs`], but generates [`Command`]s set up like [`Opener::command`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Opener::specs`].
+    pub fn commands<I, T>(&self, targets: I) -> Result<Vec<Command>>
+    where
+        I: IntoIterator<Item = T>,
+        PathOrURI: From<T>,
+    {
+        Ok(self
+            .specs(targets)?
+            .into_iter()
+            .map(|spec| self.with_stdio(Command::from(spec)))
+            .collect())
+    }
+
+    /// Generate a [`Command`] that opens the target, with its standard streams set up according
+    /// to [`Opener::stdio`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Error`].
+    pub fn command<T>(&self, target: T) -> Result<Command>
+    where
+        PathOrURI: From<T>,
+    {
+        Ok(self.with_stdio(Command::from(self.spec(target)?)))
+    }
+
+    fn with_stdio(&self, mut cmd: Command) -> Command {
+        if let Some(stdio) = self.stdio {
+            cmd.stdin(stdio.stdio())
+                .stdout(stdio.stdio())
+                .stderr(stdio.stdio());
+        }
+        cmd
+    }
+
+    /// Run the command that opens the target without waiting for it to exit.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::IO`] if the command could not be started.
+    /// - See [`Error`] for errors while generating the command.
+    pub fn spawn<T>(&self, target: T) -> Result<Child>
+    where
+        PathOrURI: From<T>,
+    {
+        Ok(self.command(target)?.spawn()?)
+    }
+
+    /// Open the target immediately, instead of returning a command.
+    ///
+    /// With the `portal` feature enabled, this uses `xdg-desktop-portal` when running inside a
+    /// Flatpak or Snap sandbox and no application or environment variable applies. Otherwise,
+    /// this runs the generated command and, if [`Opener::wait`] is set, waits for it to exit.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::ExitStatus`] if waiting and the command exits unsuccessfully.
+    /// - [`Error::WaitUnsupported`] if waiting and the target is opened through the portal.
+    /// - See [`Error`] for other possible errors.
+    pub fn launch<T>(&self, target: T) -> Result<()>
+    where
+        PathOrURI: From<T>,
+    {
+        let target = self.prepare(PathOrURI::from(target))?;
+
+        #[cfg(all(
+            feature = "portal",
+            not(any(target_os = "windows", target_os = "macos"))
+        ))]
+        if self.uses_portal()? {
+            self.policy.check_executable(&target, true)?;
+            return crate::portal::open(&target, self.portal_options);
+        }
+
+        let child = self
+            .with_stdio(Command::from(self.build(&target)?))
+            .spawn()?;
+        if self.wait {
+            self.reap(child)?;
+        }
+        Ok(())
+    }
+
+    /// Open all of the targets immediately, running the commands from [`Opener::specs`].
+    ///
+    /// At most [`Opener::max_concurrent`] commands run at the same time. Once that many are
+    /// running, the next one starts when any of them exits. If [`Opener::wait`] is set, this also
+    /// waits for the remaining commands to exit before returning. Otherwise, commands that are
+    /// still running after a short time are assumed to be the applications themselves, which are
+    /// left running and no longer counted. Like [`Opener::launch`], the portal is used instead
+    /// when running inside a sandbox.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::TooManyTargets`] if there are more targets than [`Opener::max_targets`].
+    /// - [`Error::ExitStatus`] if waiting and a command exits unsuccessfully. Commands that were
+    ///   already started keep running.
+    /// - See [`Error`] for other possible errors.
+    pub fn launch_many<I, T>(&self, targets: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        PathOrURI: From<T>,
+    {
+        #[cfg(all(
+            feature = "portal",
+            not(any(target_os = "windows", target_os = "macos"))
+        ))]
+        if self.uses_portal()? {
+            return self
+                .collect_targets(targets)?
+                .iter()
+                .try_for_each(|target| {
+                    self.policy.check_executable(target, true)?;
+                    crate::portal::open(target, self.portal_options)
+                });
+        }
+
+        let mut running = VecDeque::new();
+        for mut cmd in self.commands(targets)? {
+            self.throttle(&mut running, self.max_concurrent.max(1))?;
+            running.push_back(cmd.spawn()?);
+        }
+        self.throttle(&mut running, 1)
+    }
+
+    /// Waits until fewer than `max` of the `running` commands are still running, removing those
+    /// that exited and checking their status if [`Opener::wait`] is set.
+    ///
+    /// If not waiting and none exits within [`LAUNCH_TIMEOUT`], the oldest are left running and
+    /// removed instead, since they are likely the applications themselves.
+    fn throttle(&self, running: &mut VecDeque<Child>, max: usize) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            let mut result = Ok(());
+            running.retain_mut(|child| match child.try_wait() {
+                Ok(None) => true,
+                Ok(Some(status)) => {
+                    if self.wait && !status.success() && result.is_ok() {
+                        result = Err(Error::ExitStatus(status));
+                    }
+                    false
+                }
+                Err(error) => {
+                    if result.is_ok() {
+                        result = Err(error.into());
+                    }
+                    false
+                }
+            });
+            result?;
+
+            if running.len() < max {
+                return Ok(());
+            }
+            if !self.wait && started.elapsed() >= LAUNCH_TIMEOUT {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("leaving {} commands running", running.len() + 1 - max);
+                running.drain(..=running.len() - max);
+                return Ok(());
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Waits for a launched command to exit, checking its status if [`Opener::wait`] is set.
+    fn reap(&self, mut child: Child) -> Result<()> {
+        let status = child.wait()?;
+        if self.wait && !status.success() {
+            return Err(Error::ExitStatus(status));
+        }
+        Ok(())
+    }
+
+    /// Returns whether to open targets through the portal instead of running a command.
+    #[cfg(all(
+        feature = "portal",
+        not(any(target_os = "windows", target_os = "macos"))
+    ))]
+    fn uses_portal(&self) -> Result<bool> {
+        let uses_portal =
+            self.platform.is_current() && crate::portal::is_sandboxed() && self.uses_system();
+        if uses_portal && self.wait {
+            return Err(Error::WaitUnsupported(
+                "the desktop portal does not report when the application exits".to_string(),
+            ));
+        }
+        Ok(uses_portal)
+    }
+
+    /// Returns whether no application or environment variable overrides the system handler.
+    #[cfg(all(
+        feature = "portal",
+        not(any(target_os = "windows", target_os = "macos"))
+    ))]
+    fn uses_system(&self) -> bool {
+        self.app.is_none()
+            && self.editor_chain.is_none()
+            && self.env.iter().all(|var| {
+                let (EnvVar::Command(name) | EnvVar::BrowserList(name)) = var;
+                std::env::var_os(name).is_none()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://example.com/";
+
+    #[test]
+    fn test_app_fallback() {
+        let target = URL.parse::<PathOrURI>().unwrap();
+        let opener = Opener::new().app("surely-this-program-does-not-exist");
+
+        assert!(matches!(
+            opener.clone().fallback(false).spec(target.clone()),
+            Err(Error::NotFound { exe, .. }) if exe == "surely-this-program-does-not-exist"
+        ));
+        assert_eq!(
+            format!("{:?}", opener.spec(target.clone())),
+            format!("{:?}", Opener::new().spec(target))
+        );
+    }
+
+    #[test]
+    fn test_specs_batching() {
+        let urls = ["https://example.com/a", "https://example.com/b"];
+        let targets: Vec<PathOrURI> = urls.iter().map(|url| url.parse().unwrap()).collect();
+
+        let macos = Opener::new().platform(Platform::MacOS);
+        assert_eq!(
+            macos.specs(targets.clone()).unwrap(),
+            vec![CommandSpec::new("open").args(urls)]
+        );
+        assert_eq!(
+            macos.clone().app("Firefox").specs(targets.clone()).unwrap(),
+            vec![CommandSpec::new("open").args(["-a", "Firefox"]).args(urls)]
+        );
+
+        let windows = Opener::new().platform(Platform::Windows);
+        assert_eq!(
+            windows.specs(targets.clone()).unwrap(),
+            urls.iter()
+                .map(|target| {
+                    CommandSpec::new("cmd")
+                        .args(["/c", "start", r#""""#, target])
+                        .verbatim_args(true)
+                })
+                .collect::<Vec<_>>()
+        );
+
+        std::env::set_var("OPEN_CMD_TEST_SPECS_BATCHING", "code --reuse-window");
+        assert_eq!(
+            macos
+                .clone()
+                .env("OPEN_CMD_TEST_SPECS_BATCHING")
+                .specs(targets.clone())
+                .unwrap(),
+            vec![CommandSpec::new("code").arg("--reuse-window").args(urls)]
+        );
+        assert_eq!(
+            macos
+                .browser_env("OPEN_CMD_TEST_SPECS_BATCHING")
+                .specs(targets.clone())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_batch_merge_inserts_in_place() {
+        let template = CommandSpec::new("okular").args(["--icon", "okular"]);
+        let resolved = |target: &str| Resolved {
+            spec: CommandSpec::new("okular").args([target, "--icon", "okular"]),
+            template: Some(template.clone()),
+        };
+
+        let mut batch = resolved("a").into_batch();
+        assert!(batch.merge(&resolved("b").into_batch()));
+        assert!(!batch.merge(&Resolved::single(CommandSpec::new("okular").arg("c")).into_batch()));
+        assert_eq!(
+            batch.spec,
+            CommandSpec::new("okular").args(["a", "b", "--icon", "okular"])
+        );
+    }
+
+    #[test]
+    fn test_option_injection() {
+        let target = || PathOrURI::from(PathBuf::from("-rf"));
+        let safe = format!(".{}-rf", std::path::MAIN_SEPARATOR);
+        let macos = Opener::new().platform(Platform::MacOS);
+
+        assert_eq!(
+            macos.spec(target()).unwrap(),
+            CommandSpec::new("open").arg(&safe)
+        );
+        assert_eq!(
+            macos.clone().app("TextEdit").spec(target()).unwrap(),
+            CommandSpec::new("open").args(["-a", "TextEdit", &safe])
+        );
+
+        std::env::set_var("OPEN_CMD_TEST_OPTION_INJECTION", "vim");
+        assert_eq!(
+            macos
+                .clone()
+                .env("OPEN_CMD_TEST_OPTION_INJECTION")
+                .spec(target())
+                .unwrap(),
+            CommandSpec::new("vim").arg(&safe)
+        );
+        std::env::set_var("OPEN_CMD_TEST_OPTION_INJECTION_BROWSER", "lynx --url=%s");
+        assert_eq!(
+            macos
+                .browser_env("OPEN_CMD_TEST_OPTION_INJECTION_BROWSER")
+                .spec(target())
+                .unwrap(),
+            CommandSpec::new("lynx").arg(format!("--url={safe}"))
+        );
+    }
+
+    #[test]
+    fn test_nul_byte() {
+        let target = PathOrURI::from(PathBuf::from("a\0b"));
+        for platform in [Platform::Linux, Platform::MacOS, Platform::Windows] {
+            assert!(matches!(
+                Opener::new().platform(platform).spec(target.clone()),
+                Err(Error::NulByte(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let target = || "search-ms:query=x".parse::<PathOrURI>().unwrap();
+        let macos = Opener::new().platform(Platform::MacOS);
+        assert!(matches!(macos.spec(target()), Err(Error::Denied(_))));
+        assert_eq!(
+            macos
+                .clone()
+                .policy(OpenPolicy::allow_all())
+                .spec(target())
+                .unwrap(),
+            CommandSpec::new("open").arg("search-ms:query=x")
+        );
+        assert_eq!(
+            macos
+                .spec("file:///tmp/a.html#section".parse::<PathOrURI>().unwrap())
+                .unwrap(),
+            CommandSpec::new("open").arg("file:///tmp/a.html#section")
+        );
+
+        let asked = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = std::sync::Arc::clone(&asked);
+        let confirming = macos.policy(OpenPolicy::new().confirm(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            false
+        }));
+        assert!(matches!(
+            confirming.specs([URL, URL].map(|url| url.parse::<PathOrURI>().unwrap())),
+            Err(Error::Denied(_))
+        ));
+        assert_eq!(asked.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_executable_only_for_system_handler() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("notes");
+        std::fs::write(&script, "#!/bin/sh\nrm -rf ~\n").unwrap();
+        let refused = |result: Result<CommandSpec>| matches!(result, Err(Error::Executable { .. }));
+
+        // The file is only read when generating commands for the current platform.
+        let opener = Opener::new();
+        assert!(refused(opener.spec(script.clone())));
+        assert!(!refused(
+            opener
+                .clone()
+                .policy(OpenPolicy::new().executables(true))
+                .spec(script.clone())
+        ));
+        assert!(!refused(opener.app("cat").spec(script)));
+
+        // Relative paths are checked where the command runs.
+        assert!(refused(
+            Opener::new()
+                .current_dir(dir.path())
+                .spec(PathBuf::from("notes"))
+        ));
+    }
+
+    #[test]
+    fn test_current_dir() {
+        let spec = |platform| {
+            Opener::new()
+                .platform(platform)
+                .current_dir("/proj")
+                .spec(PathBuf::from("notes.md"))
+                .unwrap()
+        };
+        assert_eq!(
+            spec(Platform::MacOS),
+            CommandSpec::new("open")
+                .arg("/proj/notes.md")
+                .current_dir("/proj")
+        );
+        assert_eq!(
+            spec(Platform::Windows).args.last().map(String::as_str),
+            Some("file:///proj/notes.md")
+        );
+    }
+
+    #[test]
+    fn test_max_targets() {
+        let opener = Opener::new().platform(Platform::MacOS).max_targets(1);
+        assert!(matches!(
+            opener.specs([URL, URL].map(|url| url.parse::<PathOrURI>().unwrap())),
+            Err(Error::TooManyTargets { count: 2, max: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_expand() {
+        std::env::set_var("OPEN_CMD_TEST_EXPAND", "/tmp/expanded");
+        let target = PathOrURI::from(PathBuf::from("$OPEN_CMD_TEST_EXPAND/notes.md"));
+        let opener = Opener::new().platform(Platform::MacOS);
+
+        assert_eq!(
+            opener.clone().expand(true).spec(target.clone()).unwrap(),
+            CommandSpec::new("open").arg("/tmp/expanded/notes.md")
+        );
+        assert_eq!(
+            opener.spec(target).unwrap().args,
+            ["$OPEN_CMD_TEST_EXPAND/notes.md"]
+        );
+        assert!(matches!(
+            Opener::new()
+                .expand(true)
+                .spec(PathBuf::from("${OPEN_CMD_TEST_EXPAND_UNSET}/notes.md")),
+            Err(Error::UndefinedVar { var, .. }) if var == "OPEN_CMD_TEST_EXPAND_UNSET"
+        ));
+    }
+
+    #[test]
+    fn test_position() {
+        let target = PathOrURI::from(PathBuf::from("src/lib.rs"));
+        let opener = |editor: &str| {
+            let var = format!("OPEN_CMD_TEST_POSITION_{}", editor.replace(['-', ' '], "_"));
+            std::env::set_var(&var, editor);
+            Opener::new()
+                .platform(Platform::MacOS)
+                .env(var)
+                .position(42, Some(7))
+        };
+
+        assert_eq!(
+            opener("nvim").spec(target.clone()).unwrap(),
+            CommandSpec::new("nvim").args(["+call cursor(42,7)", "src/lib.rs"])
+        );
+        assert_eq!(
+            opener("code --wait").spec(target.clone()).unwrap(),
+            CommandSpec::new("code").args(["--wait", "-g", "src/lib.rs:42:7"])
+        );
+        assert_eq!(
+            opener("my-editor").spec(target.clone()).unwrap(),
+            CommandSpec::new("my-editor").arg("src/lib.rs")
+        );
+        assert_eq!(
+            opener("my-editor")
+                .editor_template(
+                    "my-editor",
+                    EditorTemplate::new(["--goto", "{line}", "{file}"])
+                )
+                .spec(target)
+                .unwrap(),
+            CommandSpec::new("my-editor").args(["--goto", "42", "src/lib.rs"])
+        );
+    }
+
+    #[test]
+    fn test_editor_chain() {
+        let target = PathOrURI::from(PathBuf::from("notes.md"));
+        std::env::set_var("OPEN_CMD_TEST_EDITOR_CHAIN", "hx");
+        let chain = EditorChain::new()
+            .env("OPEN_CMD_TEST_EDITOR_CHAIN_UNSET")
+            .env("OPEN_CMD_TEST_EDITOR_CHAIN");
+        let opener = Opener::new().platform(Platform::MacOS).fallback(false);
+
+        assert_eq!(
+            opener
+                .clone()
+                .editor_chain(chain)
+                .position(3, Some(1))
+                .spec(target.clone())
+                .unwrap(),
+            CommandSpec::new("hx").arg("notes.md:3:1")
+        );
+
+        let chain = EditorChain::new().env("OPEN_CMD_TEST_EDITOR_CHAIN_UNSET");
+        assert!(matches!(
+            opener.clone().editor_chain(chain.clone()).spec(target.clone()),
+            Err(Error::NoOpener { tried }) if tried == ["$OPEN_CMD_TEST_EDITOR_CHAIN_UNSET"]
+        ));
+        assert_eq!(
+            opener
+                .fallback(true)
+                .editor_chain(chain)
+                .spec(target)
+                .unwrap(),
+            CommandSpec::new("open").arg("notes.md")
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_launch_many_throttle() {
+        std::env::set_var("OPEN_CMD_TEST_LAUNCH_MANY", "sh -c 'sleep 10' sh");
+        let opener = Opener::new()
+            .browser_env("OPEN_CMD_TEST_LAUNCH_MANY")
+            .max_concurrent(1);
+        let targets = ["https://example.com/a", "https://example.com/b"];
+
+        // Long-running applications do not hold up the next target.
+        let started = Instant::now();
+        opener
+            .launch_many(targets.map(|url| url.parse::<PathOrURI>().unwrap()))
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        std::env::set_var("OPEN_CMD_TEST_LAUNCH_MANY_FAIL", "sh -c 'exit 3' sh");
+        assert!(matches!(
+            Opener::new()
+                .browser_env("OPEN_CMD_TEST_LAUNCH_MANY_FAIL")
+                .wait(true)
+                .launch_many(targets.map(|url| url.parse::<PathOrURI>().unwrap())),
+            Err(Error::ExitStatus(status)) if status.code() == Some(3)
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_stdio() {
+        std::env::set_var("OPEN_CMD_TEST_STDIO", "echo hello");
+        let opener = Opener::new().env("OPEN_CMD_TEST_STDIO");
+        let output = |opener: &Opener| {
+            opener
+                .command(URL.parse::<PathOrURI>().unwrap())
+                .unwrap()
+                .output()
+                .unwrap()
+                .stdout
+        };
+
+        assert_eq!(output(&opener), format!("hello {URL}\n").into_bytes());
+        assert_eq!(output(&opener.clone().stdio(StdioMode::Inherit)), b"");
+        assert_eq!(output(&opener.stdio(StdioMode::Null)), b"");
+    }
+}
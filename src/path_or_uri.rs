@@ -1,9 +1,63 @@
-use std::{fmt::Display, path::PathBuf, str::FromStr};
+use std::{
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
 
-use crate::{Error, Result};
+use crate::{expand::Environment, Error, Result, SystemEnvironment};
 use path_clean::PathClean;
 use url::Url;
 
+/// Schemes that are recognized by [`PathOrURI::parse_strict`] without a following `//`.
+const KNOWN_SCHEMES: &[&str] = &[
+    "about",
+    "bitcoin",
+    "callto",
+    "data",
+    "geo",
+    "im",
+    "irc",
+    "javascript",
+    "magnet",
+    "mailto",
+    "news",
+    "sip",
+    "sips",
+    "sms",
+    "tel",
+    "urn",
+    "vbscript",
+    "xmpp",
+];
+
+/// Errors returned by [`PathOrURI::parse_strict`].
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    /// The input was empty.
+    #[error("cannot parse an empty string as a path or URI")]
+    Empty,
+    /// The input starts with something that looks like a scheme, but it is not followed by `//`
+    /// and is not a known scheme, so it could be either a relative path or a URI.
+    #[error(
+        "{input:?} is ambiguous: {scheme:?} is not a known URI scheme and is not followed by `//`"
+    )]
+    Ambiguous {
+        /// The input that was parsed.
+        input: String,
+        /// The part before the first `:`.
+        scheme: String,
+    },
+    /// The input looks like a URI, but is not a valid one.
+    #[error("{input:?} is not a valid URI: {error}")]
+    InvalidUri {
+        /// The input that was parsed.
+        input: String,
+        /// The error returned while parsing the URI.
+        #[source]
+        error: url::ParseError,
+    },
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[allow(variant_size_differences)]
 /// A local file path or a remote URI.
@@ -23,8 +77,8 @@ impl PathOrURI {
 
     /// Returns whether the contained value is a URI.
     ///
-    /// When created from a URI using [`From`], a `file://` URI will be converted to a [`PathBuf`]
-    /// and this method will return `false`.
+    /// When created from a URI using [`From`], a `file://` URI that refers to a local path will be
+    /// converted to a [`PathBuf`] and this method will return `false`.
     #[must_use]
     pub fn is_uri(&self) -> bool {
         matches!(self, PathOrURI::URI(_))
@@ -36,16 +90,146 @@ impl PathOrURI {
     ///
     /// - The cleaned path does not resolve to an absolute path.
     /// - If the contained path is relative and the current directory does not exist or cannot be
-    /// accessed (i.e. [`std::env::current_dir`] fails).
+    ///   accessed (i.e. [`std::env::current_dir`] fails).
     pub fn uri(&self) -> Result<Url> {
         match self {
             Self::URI(url) => Ok(url.clone()),
             Self::Path(path) => {
                 let new_path = std::env::current_dir()?.join(path).clean();
-                Url::from_file_path(new_path).map_err(|_| Error::FileToURI(path.clone()))
+                Url::from_file_path(new_path).map_err(|()| Error::FileToURI(path.clone()))
+            }
+        }
+    }
+
+    /// Expands a leading `~` or `~user` and the environment variables in a path, using the
+    /// environment of the current process. URIs are returned unchanged.
+    ///
+    /// Variables may be written as `$NAME`, `${NAME}`, or `%NAME%`, so that both
+    /// `$XDG_DOCUMENTS_DIR/report.pdf` and `%USERPROFILE%\Desktop` work. Text that does not form a
+    /// variable, such as a lone `$` or `%`, is kept as-is. Paths that are not valid Unicode are
+    /// returned unchanged.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::UndefinedVar`] if a variable is not set.
+    /// - [`Error::UnknownHome`] if the home directory for `~` cannot be determined.
+    pub fn expand(&self) -> Result<Self> {
+        self.expand_with(&SystemEnvironment)
+    }
+
+    /// Like [`PathOrURI::expand`], but reads variables and home directories from `env`.
+    ///
+    /// # Errors
+    ///
+    /// See [`PathOrURI::expand`].
+    pub fn expand_with<E: Environment + ?Sized>(&self, env: &E) -> Result<Self> {
+        match self {
+            Self::Path(path) => crate::expand::expand(path, env).map(Self::Path),
+            Self::URI(_) => Ok(self.clone()),
+        }
+    }
+
+    /// Ensures the target can be passed to a program.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::NulByte`] if the path contains a NUL byte, which cannot be part of an argument.
+    pub(crate) fn validate(&self) -> Result<()> {
+        match self {
+            Self::Path(path) if path.as_os_str().as_encoded_bytes().contains(&0) => {
+                Err(Error::NulByte(path.clone()))
             }
+            _ => Ok(()),
         }
     }
+
+    /// Returns the target as a command-line argument that cannot be mistaken for something else.
+    ///
+    /// Relative paths that could be read as an option (`-rf`) or a URI (`mailto:foo`) are
+    /// prefixed with `./`.
+    ///
+    /// # Errors
+    ///
+    /// See [`PathOrURI::validate`].
+    pub(crate) fn to_arg(&self) -> Result<String> {
+        self.validate()?;
+        Ok(match self {
+            Self::Path(path) if is_ambiguous(path) => {
+                Path::new(".").join(path).display().to_string()
+            }
+            target => target.to_string(),
+        })
+    }
+
+    /// Parses `s` as a path or URI, failing instead of guessing when it is ambiguous.
+    ///
+    /// Unlike the lenient [`FromStr`] implementation, which treats anything that [`Url`] accepts as
+    /// a URI, this returns a URI only if `s` starts with `scheme://` or one of a list of known
+    /// schemes that are used without `//`, such as `mailto:`. Windows drive letter paths like
+    /// `C:\Users` and UNC paths like `\\server\share` are always paths. Other inputs with a
+    /// `name:` prefix, like `notes:draft.txt`, are rejected as ambiguous. Everything else is a
+    /// path.
+    ///
+    /// # Errors
+    ///
+    /// See [`ParseError`].
+    pub fn parse_strict(s: &str) -> std::result::Result<Self, ParseError> {
+        if s.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        if is_windows_path(s) {
+            return Ok(Self::from(PathBuf::from(s)));
+        }
+
+        let Some((scheme, rest)) = s.split_once(':').filter(|(scheme, _)| is_scheme(scheme)) else {
+            return Ok(Self::from(PathBuf::from(s)));
+        };
+
+        let lower = scheme.to_ascii_lowercase();
+        if !rest.starts_with("//") && !KNOWN_SCHEMES.contains(&lower.as_str()) {
+            return Err(ParseError::Ambiguous {
+                input: s.to_string(),
+                scheme: scheme.to_string(),
+            });
+        }
+
+        s.parse::<Url>()
+            .map(Self::from)
+            .map_err(|error| ParseError::InvalidUri {
+                input: s.to_string(),
+                error,
+            })
+    }
+}
+
+/// Returns whether a program could read `path` as an option or a URI instead of a path.
+fn is_ambiguous(path: &Path) -> bool {
+    match path.components().next() {
+        Some(Component::Normal(first)) => {
+            let first = first.to_string_lossy();
+            first.starts_with('-') || first.contains(':')
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether `s` is a Windows path with a drive letter (`C:`, `C:\dir`, `C:/dir`) or a UNC
+/// path (`\\server\share`).
+fn is_windows_path(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let drive = bytes.len() >= 2
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && bytes.get(2).is_none_or(|&c| c == b'\\' || c == b'/');
+    drive || s.starts_with(r"\\")
+}
+
+/// Returns whether `s` is a valid URI scheme: a letter followed by letters, digits, `+`, `-`, or
+/// `.`.
+fn is_scheme(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
 }
 
 impl FromStr for PathOrURI {
@@ -65,11 +249,22 @@ impl From<PathBuf> for PathOrURI {
     }
 }
 
+/// Converts `file://` URIs to paths, decoding percent-escapes.
+///
+/// A `file://` URI is kept as a URI if it cannot be represented by a local path without losing
+/// information: if it has a query or fragment, or a host other than `localhost` that is not a UNC
+/// server on Windows.
 impl From<Url> for PathOrURI {
     fn from(value: Url) -> Self {
-        if value.scheme() == "file" {
-            Self::from(PathBuf::from(value.path()))
+        if value.scheme() != "file" || value.query().is_some() || value.fragment().is_some() {
+            return Self::URI(value);
+        }
+
+        if let Ok(path) = value.to_file_path() {
+            Self::Path(path)
         } else {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("{} does not refer to a local path", value);
             Self::URI(value)
         }
     }
@@ -79,7 +274,7 @@ impl Display for PathOrURI {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Path(path) => write!(f, "{}", path.display()),
-            Self::URI(uri) => write!(f, "{}", uri),
+            Self::URI(uri) => write!(f, "{uri}"),
         }
     }
 }
@@ -107,6 +302,38 @@ mod tests {
         assert_eq!(PathOrURI::from(url), PathOrURI::Path(path),);
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_from_file_uri_decodes() {
+        let url: Url = "file:///tmp/my%20file%23%25.txt".parse().unwrap();
+        assert_eq!(
+            PathOrURI::from(url),
+            PathOrURI::Path(PathBuf::from("/tmp/my file#%.txt"))
+        );
+        let url: Url = "file://localhost/tmp/caf%C3%A9".parse().unwrap();
+        assert_eq!(
+            PathOrURI::from(url),
+            PathOrURI::Path(PathBuf::from("/tmp/café"))
+        );
+    }
+
+    #[test]
+    fn test_from_file_uri_lossy() {
+        for uri in [
+            "file://server/share/file.txt?query",
+            "file:///tmp/file.txt?query",
+            "file:///tmp/file.txt#fragment",
+        ] {
+            let url: Url = uri.parse().unwrap();
+            assert_eq!(PathOrURI::from(url.clone()), PathOrURI::URI(url), "{uri}");
+        }
+        #[cfg(unix)]
+        {
+            let url: Url = "file://server/share/file.txt".parse().unwrap();
+            assert_eq!(PathOrURI::from(url.clone()), PathOrURI::URI(url));
+        }
+    }
+
     #[test]
     fn test_from_str() {
         assert_eq!(
@@ -125,6 +352,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_strict_paths() {
+        for path in [
+            r"C:\Users\me\file.txt",
+            "c:/Users/me/file.txt",
+            "D:",
+            r"\\server\share\file.txt",
+            "/home/me/file.txt",
+            "relative/file.txt",
+            "file.txt",
+        ] {
+            assert_eq!(
+                PathOrURI::parse_strict(path).unwrap(),
+                PathOrURI::Path(PathBuf::from(path)),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_strict_uris() {
+        for uri in [
+            "https://example.com/a?b=c",
+            "mailto:someone@example.com",
+            "MAILTO:someone@example.com",
+            "tel:+1-555-0100",
+            "custom-app://open/thing",
+        ] {
+            assert_eq!(
+                PathOrURI::parse_strict(uri).unwrap(),
+                PathOrURI::URI(uri.parse().unwrap()),
+                "{uri}"
+            );
+        }
+        assert_eq!(
+            PathOrURI::parse_strict("file:///test/path").unwrap(),
+            PathOrURI::Path(PathBuf::from("/test/path"))
+        );
+    }
+
+    #[test]
+    fn test_parse_strict_errors() {
+        assert_eq!(PathOrURI::parse_strict(""), Err(ParseError::Empty));
+        assert_eq!(
+            PathOrURI::parse_strict("foo:bar.txt"),
+            Err(ParseError::Ambiguous {
+                input: "foo:bar.txt".into(),
+                scheme: "foo".into()
+            })
+        );
+        assert!(matches!(
+            PathOrURI::parse_strict("https://exa mple.com"),
+            Err(ParseError::InvalidUri { .. })
+        ));
+        // The lenient parser still guesses.
+        assert!(PathOrURI::from_str("foo:bar.txt").unwrap().is_uri());
+    }
+
     #[test]
     fn test_is_uri() {
         assert!(PathOrURI::URI("https://example.com".parse().unwrap()).is_uri());
@@ -137,6 +422,28 @@ mod tests {
         assert!(PathOrURI::Path(PathBuf::from("/test/path")).is_path());
     }
 
+    #[test]
+    fn test_to_arg() {
+        let arg = |path: &str| PathOrURI::Path(PathBuf::from(path)).to_arg().unwrap();
+        assert_eq!(arg("-rf"), format!(".{}-rf", std::path::MAIN_SEPARATOR));
+        assert_eq!(
+            arg("mailto:foo"),
+            format!(".{}mailto:foo", std::path::MAIN_SEPARATOR)
+        );
+        assert_eq!(arg("notes/-draft.txt"), "notes/-draft.txt");
+        assert_eq!(arg("/tmp/-rf"), "/tmp/-rf");
+        assert_eq!(arg("./--help"), "./--help");
+
+        let uri = PathOrURI::from_str("mailto:foo@example.com").unwrap();
+        assert_eq!(uri.to_arg().unwrap(), "mailto:foo@example.com");
+    }
+
+    #[test]
+    fn test_nul_byte() {
+        let target = PathOrURI::Path(PathBuf::from("evil\0name"));
+        assert!(matches!(target.to_arg(), Err(Error::NulByte(_))));
+    }
+
     #[test]
     fn test_to_uri() {
         let uri: Url = "https://example.com/test/path".parse().unwrap();
@@ -147,4 +454,23 @@ mod tests {
         assert_eq!(uri.clone(), PathOrURI::URI(uri).uri().unwrap());
         assert_eq!(path_uri, PathOrURI::Path(path).uri().unwrap());
     }
+
+    proptest::proptest! {
+        #[test]
+        fn test_file_uri_round_trip(
+            segments in proptest::collection::vec("[a-zA-Z0-9 #%?&;=+~'ü日本é._-]{1,12}", 1..5)
+        ) {
+            // `.` and `..` are cleaned away by `uri()`, so they cannot round-trip.
+            proptest::prop_assume!(segments.iter().all(|s| s != "." && s != ".."));
+
+            let root = if cfg!(windows) { r"C:\" } else { "/" };
+            let mut path = PathBuf::from(root);
+            path.extend(&segments);
+
+            let target = PathOrURI::Path(path.clone());
+            let uri = target.uri().unwrap();
+            proptest::prop_assert!(uri.query().is_none() && uri.fragment().is_none());
+            proptest::prop_assert_eq!(PathOrURI::from(uri), target);
+        }
+    }
 }
@@ -0,0 +1,308 @@
+use crate::{
+    linux, macos, windows, CommandSpec, Environment, PathOrURI, SystemEnvironment, WindowsStrategy,
+};
+
+/// Options that affect how platform-specific commands are generated.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct PlatformOptions<'a> {
+    /// Generate commands that only exit once the handler application exits.
+    pub(crate) wait: bool,
+    /// How targets are opened on Windows.
+    pub(crate) windows: WindowsStrategy,
+    /// The environment of the system that commands are generated for, which is checked for
+    /// programs and WSL. If `None`, this is the current system when generating commands for it,
+    /// and nothing is checked when generating commands for another platform.
+    pub(crate) env: Option<&'a dyn Environment>,
+}
+
+impl<'a> PlatformOptions<'a> {
+    /// Returns the environment to check when generating commands for `platform`, if any.
+    pub(crate) fn env(self, platform: Platform) -> Option<&'a dyn Environment> {
+        let system: &dyn Environment = &SystemEnvironment;
+        self.env.or_else(|| platform.is_current().then_some(system))
+    }
+
+    /// Returns an error if `cmd` does not exist when generating commands for `platform`, if that
+    /// can be checked.
+    pub(crate) fn ensure_command(self, platform: Platform, cmd: &str) -> crate::Result<()> {
+        match self.env(platform) {
+            Some(env) => crate::ensure_command_in(env, cmd),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An operating system to generate commands for.
+///
+/// Commands can be generated for any platform, regardless of which platform this crate was
+/// compiled for. Executables are only checked for existence when generating commands for the
+/// current platform, since they cannot be looked up on any other system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// Linux and other Unix-like systems that follow freedesktop.org standards.
+    Linux,
+    /// Apple's macOS.
+    MacOS,
+    /// Microsoft Windows.
+    Windows,
+}
+
+impl Platform {
+    /// Returns the platform this crate was compiled for.
+    #[must_use]
+    pub const fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            Self::Windows
+        } else if cfg!(target_os = "macos") {
+            Self::MacOS
+        } else {
+            Self::Linux
+        }
+    }
+
+    /// Returns whether this is the platform this crate was compiled for.
+    #[must_use]
+    pub fn is_current(self) -> bool {
+        self == Self::current()
+    }
+
+    /// Generate a command that opens the target in this platform's default handler.
+    pub(crate) fn open(
+        self,
+        target: &PathOrURI,
+        options: PlatformOptions<'_>,
+    ) -> crate::Result<CommandSpec> {
+        match self {
+            Self::Linux => linux::open(target, options),
+            Self::MacOS => macos::open(target, options),
+            Self::Windows => windows::open(target, options),
+        }
+    }
+
+    /// Generate a command that opens the target with the given application.
+    pub(crate) fn open_with(
+        self,
+        app: &str,
+        target: &PathOrURI,
+        options: PlatformOptions<'_>,
+    ) -> crate::Result<CommandSpec> {
+        match self {
+            Self::Linux => linux::open_with(app, target, options),
+            Self::MacOS => macos::open_with(app, target, options),
+            Self::Windows => windows::open_with(app, target, options),
+        }
+    }
+
+    /// Returns the command that `spec`, generated for a single target by [`Platform::open`] or
+    /// [`Platform::open_with`], would be without any targets, if it also accepts several.
+    pub(crate) fn batch_template(
+        self,
+        app: Option<&str>,
+        spec: &CommandSpec,
+    ) -> Option<CommandSpec> {
+        match (self, app) {
+            (Self::MacOS, _) => Some(spec.without_last_arg()),
+            (Self::Linux, Some(app)) => linux::open_with_template(app, spec),
+            (Self::Linux | Self::Windows, _) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::FakeSystem, Opener};
+
+    const URL: &str = "https://example.com/page";
+
+    fn spec_for(platform: Platform) -> CommandSpec {
+        crate::open_for_spec(platform, URL.parse::<PathOrURI>().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_current() {
+        assert!(Platform::current().is_current());
+        #[cfg(target_os = "linux")]
+        assert_eq!(Platform::current(), Platform::Linux);
+    }
+
+    #[test]
+    fn test_open_windows() {
+        assert_eq!(
+            spec_for(Platform::Windows),
+            CommandSpec::new("cmd")
+                .args(["/c", "start", r#""""#, URL])
+                .verbatim_args(true)
+        );
+    }
+
+    #[test]
+    fn test_open_macos() {
+        assert_eq!(spec_for(Platform::MacOS), CommandSpec::new("open").arg(URL));
+    }
+
+    #[test]
+    fn test_open_with_windows() {
+        let spec = Opener::new()
+            .platform(Platform::Windows)
+            .app("firefox.exe")
+            .spec(URL.parse::<PathOrURI>().unwrap())
+            .unwrap();
+        assert_eq!(
+            spec,
+            CommandSpec::new("cmd")
+                .args(["/c", "start", r#""""#, "firefox.exe", URL])
+                .verbatim_args(true)
+        );
+    }
+
+    #[test]
+    fn test_open_with_macos() {
+        let open_with = |app| {
+            Opener::new()
+                .platform(Platform::MacOS)
+                .app(app)
+                .spec(URL.parse::<PathOrURI>().unwrap())
+                .unwrap()
+        };
+        assert_eq!(
+            open_with("Firefox"),
+            CommandSpec::new("open").args(["-a", "Firefox", URL])
+        );
+        assert_eq!(
+            open_with("org.mozilla.firefox"),
+            CommandSpec::new("open").args(["-b", "org.mozilla.firefox", URL])
+        );
+    }
+
+    #[test]
+    fn test_open_wait() {
+        let wait_for = |platform, app: Option<&str>| {
+            let opener = Opener::new().platform(platform).wait(true);
+            app.map_or(opener.clone(), |app| opener.app(app))
+                .spec(URL.parse::<PathOrURI>().unwrap())
+        };
+        assert_eq!(
+            wait_for(Platform::MacOS, None).unwrap(),
+            CommandSpec::new("open").args(["-W", URL])
+        );
+        assert_eq!(
+            wait_for(Platform::MacOS, Some("Firefox")).unwrap(),
+            CommandSpec::new("open").args(["-W", "-a", "Firefox", URL])
+        );
+        assert_eq!(
+            wait_for(Platform::Windows, None).unwrap(),
+            CommandSpec::new("cmd")
+                .args(["/c", "start", "/wait", r#""""#, URL])
+                .verbatim_args(true)
+        );
+        assert_eq!(
+            wait_for(Platform::Windows, Some("notepad.exe")).unwrap(),
+            CommandSpec::new("cmd")
+                .args(["/c", "start", "/wait", r#""""#, "notepad.exe", URL])
+                .verbatim_args(true)
+        );
+        if !Platform::Linux.is_current() {
+            assert!(matches!(
+                wait_for(Platform::Linux, None),
+                Err(crate::Error::WaitUnsupported(_))
+            ));
+        }
+    }
+
+    fn options(env: &FakeSystem) -> PlatformOptions<'_> {
+        PlatformOptions {
+            env: Some(env),
+            ..PlatformOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_open_linux() {
+        let target = URL.parse::<PathOrURI>().unwrap();
+        let open = |env: FakeSystem| Platform::Linux.open(&target, options(&env));
+        assert_eq!(
+            open(FakeSystem::default().program("xdg-open").program("gio")).unwrap(),
+            CommandSpec::new("xdg-open").arg(URL)
+        );
+        assert_eq!(
+            open(FakeSystem::default().program("gio")).unwrap(),
+            CommandSpec::new("gio").args(["open", "--", URL])
+        );
+        assert!(matches!(
+            open(FakeSystem::default()),
+            Err(crate::Error::NoOpener { .. })
+        ));
+    }
+
+    #[test]
+    fn test_open_wsl() {
+        let target = "https://example.com/?a=1&calc"
+            .parse::<PathOrURI>()
+            .unwrap();
+        let open = |env: FakeSystem| {
+            let env = env.var("WSL_DISTRO_NAME", "Ubuntu").program("xdg-open");
+            Platform::Linux.open(&target, options(&env))
+        };
+        assert_eq!(
+            open(FakeSystem::default().program("wslview").program("cmd.exe")).unwrap(),
+            CommandSpec::new("wslview").arg("https://example.com/?a=1&calc")
+        );
+        assert_eq!(
+            open(FakeSystem::default().program("cmd.exe")).unwrap(),
+            CommandSpec::new("cmd.exe")
+                .args(["/c", "start", r#""""#, "https://example.com/?a=1^&calc"])
+                .verbatim_args(true)
+        );
+        assert_eq!(
+            open(FakeSystem::default().program("explorer.exe")).unwrap(),
+            CommandSpec::new("explorer.exe")
+                .arg(r#""https://example.com/?a=1&calc""#)
+                .verbatim_args(true)
+        );
+    }
+
+    #[test]
+    fn test_open_with_linux() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path().join("applications");
+        std::fs::create_dir_all(&apps).unwrap();
+        std::fs::write(
+            apps.join("org.kde.okular.desktop"),
+            "[Desktop Entry]\nExec=okular --unique %U\n",
+        )
+        .unwrap();
+        let data_dir = dir.path().to_str().unwrap();
+        let env = FakeSystem::default()
+            .var("XDG_DATA_HOME", data_dir)
+            .var("XDG_DATA_DIRS", data_dir)
+            .program("okular")
+            .program("firefox");
+
+        let target = URL.parse::<PathOrURI>().unwrap();
+        let open_with = |app| Platform::Linux.open_with(app, &target, options(&env));
+        assert_eq!(
+            open_with("org.kde.okular.desktop").unwrap(),
+            CommandSpec::new("okular").args(["--unique", URL])
+        );
+        assert_eq!(
+            open_with("firefox --new-window").unwrap(),
+            CommandSpec::new("firefox").args(["--new-window", URL])
+        );
+        assert!(matches!(
+            open_with("chromium"),
+            Err(crate::Error::NotFound { .. })
+        ));
+
+        if !Platform::Linux.is_current() {
+            assert_eq!(
+                Opener::new()
+                    .platform(Platform::Linux)
+                    .app("org.kde.okular.desktop")
+                    .spec(target.clone())
+                    .unwrap(),
+                CommandSpec::new("gtk-launch").args(["org.kde.okular.desktop", URL])
+            );
+        }
+    }
+}
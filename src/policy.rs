@@ -0,0 +1,294 @@
+//! Deciding which targets may be opened at all.
+
+use std::{fmt, path::PathBuf, sync::Arc};
+
+use url::Url;
+
+use crate::{Error, PathOrURI, Result};
+
+/// The schemes allowed by the default policy.
+const DEFAULT_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// A function that confirms or rejects a target.
+#[derive(Clone)]
+struct Confirm(Arc<dyn Fn(&PathOrURI) -> bool + Send + Sync>);
+
+impl fmt::Debug for Confirm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Confirm(..)")
+    }
+}
+
+impl PartialEq for Confirm {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Confirm {}
+
+/// Which targets may be opened, checked before any command is generated.
+///
+/// URIs are allowed or denied by scheme, and paths as a whole. `file://` URIs that refer to the
+/// local system, such as ones with a fragment, are also allowed along with paths. Denied schemes
+/// take precedence over allowed ones. Targets that pass these checks are then given to the
+/// function set with [`OpenPolicy::confirm`], if any.
+///
+/// When a path is opened with the system handler, which may run it instead of opening it, the
+/// policy also refuses files that look executable unless [`OpenPolicy::executables`] is set.
+/// This is decided by the extension (e.g. `.sh`, `.exe`, `.desktop`, or `.jar`) and, for files
+/// on the current system, by the executable bit and magic bytes (ELF, shebang, `MZ`, Mach-O, or
+/// a `[Desktop Entry]` group).
+///
+/// The default policy only allows paths and the `http`, `https`, and `mailto` schemes, since the
+/// handlers of other schemes (such as `ms-msdt:` or `search-ms:`) have had remote code execution
+/// vulnerabilities. Use [`OpenPolicy::allow_all`] to open any scheme, e.g. when the targets come
+/// from a trusted source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpenPolicy {
+    /// The allowed schemes, or `None` to allow all that are not denied.
+    allowed: Option<Vec<String>>,
+    denied: Vec<String>,
+    paths: bool,
+    executables: bool,
+    confirm: Option<Confirm>,
+}
+
+impl Default for OpenPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenPolicy {
+    /// Create the default policy, which allows paths and the `http`, `https`, and `mailto`
+    /// schemes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            allowed: Some(DEFAULT_SCHEMES.iter().map(ToString::to_string).collect()),
+            denied: Vec::new(),
+            paths: true,
+            executables: false,
+            confirm: None,
+        }
+    }
+
+    /// Create a policy that allows paths and every scheme.
+    #[must_use]
+    pub fn allow_all() -> Self {
+        Self {
+            allowed: None,
+            ..Self::new()
+        }
+    }
+
+    /// Create a policy that allows nothing, to add specific schemes to with
+    /// [`OpenPolicy::allow`] and paths to with [`OpenPolicy::paths`].
+    #[must_use]
+    pub fn deny_all() -> Self {
+        Self {
+            allowed: Some(Vec::new()),
+            paths: false,
+            ..Self::new()
+        }
+    }
+
+    /// Allow URIs with the given scheme, unless it is also denied.
+    #[must_use]
+    pub fn allow<S: AsRef<str>>(mut self, scheme: S) -> Self {
+        if let Some(allowed) = &mut self.allowed {
+            allowed.push(scheme.as_ref().to_ascii_lowercase());
+        }
+        self
+    }
+
+    /// Deny URIs with the given scheme, even if it is allowed.
+    #[must_use]
+    pub fn deny<S: AsRef<str>>(mut self, scheme: S) -> Self {
+        self.denied.push(scheme.as_ref().to_ascii_lowercase());
+        self
+    }
+
+    /// Whether to allow paths. Defaults to `true`.
+    #[must_use]
+    pub fn paths(mut self, allow: bool) -> Self {
+        self.paths = allow;
+        self
+    }
+
+    /// Whether to let the system handler open files that look executable, which it may run.
+    /// Defaults to `false`.
+    ///
+    /// This is only checked when the target is opened with the system handler. Applications chosen
+    /// with [`Opener::app`](crate::Opener::app), an environment variable, or an editor chain are
+    /// trusted to open files instead of running them.
+    #[must_use]
+    pub fn executables(mut self, allow: bool) -> Self {
+        self.executables = allow;
+        self
+    }
+
+    /// Ask `confirm` whether to open each target that the rest of the policy allows, e.g. by
+    /// prompting the user. Targets are only opened if it returns `true`.
+    #[must_use]
+    pub fn confirm<F>(mut self, confirm: F) -> Self
+    where
+        F: Fn(&PathOrURI) -> bool + Send + Sync + 'static,
+    {
+        self.confirm = Some(Confirm(Arc::new(confirm)));
+        self
+    }
+
+    /// Returns whether the policy allows the target, without asking the confirmation function.
+    #[must_use]
+    pub fn allows(&self, target: &PathOrURI) -> bool {
+        match target {
+            PathOrURI::Path(_) => self.paths,
+            PathOrURI::URI(uri) => {
+                let scheme = uri.scheme();
+                !self.denied.iter().any(|denied| denied == scheme)
+                    && (local_file_path(uri).is_some() && self.paths
+                        || self
+                            .allowed
+                            .as_ref()
+                            .is_none_or(|allowed| allowed.iter().any(|a| a == scheme)))
+            }
+        }
+    }
+
+    /// Ensures the target may be opened, asking the confirmation function if it is allowed.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Denied`] if the policy or the confirmation function rejects the target.
+    pub fn check(&self, target: &PathOrURI) -> Result<()> {
+        if !self.allows(target) {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("{} is not allowed by the policy", target);
+            return Err(Error::Denied(target.to_string()));
+        }
+
+        if let Some(Confirm(confirm)) = &self.confirm {
+            if !confirm(target) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("opening {} was not confirmed", target);
+                return Err(Error::Denied(target.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Ensures the system handler will not run the target, if it is a path. Files are only read if
+    /// `local` is set, i.e. the command runs on the current system.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Executable`] if the target looks executable and executables are not allowed.
+    pub(crate) fn check_executable(&self, target: &PathOrURI, local: bool) -> Result<()> {
+        if self.executables {
+            return Ok(());
+        }
+        let path = match target {
+            PathOrURI::Path(path) => path.clone(),
+            PathOrURI::URI(uri) => match local_file_path(uri) {
+                Some(path) => path,
+                None => return Ok(()),
+            },
+        };
+
+        match crate::executable::reason(&path, local) {
+            Some(reason) => Err(Error::Executable { path, reason }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the path of a `file://` URI that refers to the local system, e.g. one that
+/// [`PathOrURI`] kept as a URI because it has a fragment.
+fn local_file_path(uri: &Url) -> Option<PathBuf> {
+    if uri.scheme() != "file" || !matches!(uri.host_str(), None | Some("" | "localhost")) {
+        return None;
+    }
+    uri.to_file_path().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(uri: &str) -> PathOrURI {
+        uri.parse().unwrap()
+    }
+
+    #[test]
+    fn test_default() {
+        let policy = OpenPolicy::default();
+        assert!(policy.allows(&uri("https://example.com")));
+        assert!(policy.allows(&uri("HTTP://example.com")));
+        assert!(policy.allows(&uri("mailto:someone@example.com")));
+        assert!(policy.allows(&PathOrURI::from(PathBuf::from("notes.txt"))));
+        assert!(policy.allows(&uri("file:///tmp/a.html#section")));
+        assert!(policy.allows(&uri("file://localhost/tmp/a.html?q=1")));
+        for denied in [
+            "file://server/share/a.html",
+            "javascript:alert(1)",
+            "vbscript:msgbox",
+            "ms-msdt:/id PCWDiagnostic",
+            "search-ms:query=x",
+            "ftp://example.com",
+        ] {
+            assert!(!policy.allows(&uri(denied)), "{denied} should be denied");
+        }
+    }
+
+    #[test]
+    fn test_allow_and_deny() {
+        let policy = OpenPolicy::allow_all().deny("JavaScript");
+        assert!(policy.allows(&uri("ftp://example.com")));
+        assert!(!policy.allows(&uri("javascript:alert(1)")));
+
+        let policy = OpenPolicy::deny_all().allow("https").deny("https");
+        assert!(!policy.allows(&uri("https://example.com")));
+        assert!(!policy.allows(&PathOrURI::from(PathBuf::from("notes.txt"))));
+        assert!(!policy.allows(&uri("file:///tmp/a.html#section")));
+        assert!(!OpenPolicy::new()
+            .deny("file")
+            .allows(&uri("file:///tmp/a.html#section")));
+    }
+
+    #[test]
+    fn test_executables() {
+        let script = PathOrURI::from(PathBuf::from("install.sh"));
+        assert!(matches!(
+            OpenPolicy::new().check_executable(&script, false),
+            Err(Error::Executable { .. })
+        ));
+        assert!(OpenPolicy::new()
+            .executables(true)
+            .check_executable(&script, false)
+            .is_ok());
+        assert!(OpenPolicy::new()
+            .check_executable(&uri("https://example.com/install.sh"), false)
+            .is_ok());
+        assert!(matches!(
+            OpenPolicy::new().check_executable(&uri("file:///tmp/install.sh#top"), false),
+            Err(Error::Executable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_confirm() {
+        let policy = OpenPolicy::new().confirm(|target| target.to_string().contains("good"));
+        assert!(policy.check(&uri("https://good.example.com")).is_ok());
+        assert!(matches!(
+            policy.check(&uri("https://bad.example.com")),
+            Err(Error::Denied(target)) if target == "https://bad.example.com/"
+        ));
+        assert!(matches!(
+            policy.check(&uri("javascript:good")),
+            Err(Error::Denied(_))
+        ));
+    }
+}
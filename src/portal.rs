@@ -0,0 +1,201 @@
+//! Opening targets through the [OpenURI portal] of `xdg-desktop-portal`.
+//!
+//! Inside a Flatpak or Snap sandbox, the usual openers either do not exist or cannot see the
+//! applications installed on the host. The portal is the supported way to ask the host to open
+//! something on the sandboxed application's behalf.
+//!
+//! [OpenURI portal]: https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.OpenURI.html
+
+use std::{collections::HashMap, fs::File, path::Path};
+
+use zbus::{blocking::Connection, zvariant::Value};
+
+use crate::{PathOrURI, Result};
+
+const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_INTERFACE: &str = "org.freedesktop.portal.OpenURI";
+const FLATPAK_INFO_FILE: &str = "/.flatpak-info";
+const SNAP_ENV: &str = "SNAP";
+
+/// Options passed to the portal along with the target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PortalOptions {
+    /// Ask the user to choose an application, even if a default is set.
+    pub ask: bool,
+    /// Allow the chosen application to write to the file. Only used when opening paths.
+    pub writable: bool,
+}
+
+impl PortalOptions {
+    fn to_dict(self, include_writable: bool) -> HashMap<&'static str, Value<'static>> {
+        let mut dict = HashMap::new();
+        dict.insert("ask", Value::from(self.ask));
+        if include_writable {
+            dict.insert("writable", Value::from(self.writable));
+        }
+        dict
+    }
+}
+
+/// Returns whether this process is running inside a Flatpak or Snap sandbox.
+#[must_use]
+pub fn is_sandboxed() -> bool {
+    Path::new(FLATPAK_INFO_FILE).exists() || std::env::var_os(SNAP_ENV).is_some()
+}
+
+/// Ask the portal on `connection` to open the target.
+///
+/// URIs are opened with `OpenURI`. Paths are opened and passed to `OpenFile` as a file descriptor,
+/// since the host cannot necessarily see paths inside the sandbox.
+fn open_on(connection: &Connection, target: &PathOrURI, options: PortalOptions) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    tracing::debug!("opening {} with xdg-desktop-portal", target);
+
+    match target {
+        PathOrURI::URI(uri) => {
+            connection.call_method(
+                Some(PORTAL_DESTINATION),
+                PORTAL_PATH,
+                Some(PORTAL_INTERFACE),
+                "OpenURI",
+                &("", uri.as_str(), options.to_dict(false)),
+            )?;
+        }
+        PathOrURI::Path(path) => {
+            let file = File::options()
+                .read(true)
+                .write(options.writable)
+                .open(path)?;
+            connection.call_method(
+                Some(PORTAL_DESTINATION),
+                PORTAL_PATH,
+                Some(PORTAL_INTERFACE),
+                "OpenFile",
+                &("", zbus::zvariant::Fd::from(&file), options.to_dict(true)),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask `xdg-desktop-portal` on the session bus to open the target.
+///
+/// Unlike most functions in this crate, this opens the target immediately instead of returning a
+/// command.
+///
+/// # Errors
+///
+/// - [`Error::DBus`](crate::Error::DBus) if the session bus or portal cannot be reached.
+/// - [`Error::IO`](crate::Error::IO) if the target is a path that cannot be opened.
+pub fn open(target: &PathOrURI, options: PortalOptions) -> Result<()> {
+    open_on(&Connection::session()?, target, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Seek},
+        sync::{Arc, Mutex},
+    };
+
+    use zbus::{interface, zvariant::OwnedFd};
+
+    use super::*;
+    use crate::test_util::PrivateBus;
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Uri(String, bool),
+        File(String, bool, bool),
+    }
+
+    struct StubPortal(Arc<Mutex<Vec<Call>>>);
+
+    fn flag(options: &HashMap<String, zbus::zvariant::OwnedValue>, key: &str) -> bool {
+        options
+            .get(key)
+            .and_then(|value| bool::try_from(value).ok())
+            .unwrap_or_default()
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    #[interface(name = "org.freedesktop.portal.OpenURI")]
+    impl StubPortal {
+        #[zbus(name = "OpenURI")]
+        fn open_uri(
+            &self,
+            parent_window: &str,
+            uri: &str,
+            options: HashMap<String, zbus::zvariant::OwnedValue>,
+        ) -> zbus::zvariant::OwnedObjectPath {
+            assert_eq!(parent_window, "");
+            let call = Call::Uri(uri.to_string(), flag(&options, "ask"));
+            self.0.lock().unwrap().push(call);
+            zbus::zvariant::ObjectPath::from_static_str_unchecked("/request/1").into()
+        }
+
+        fn open_file(
+            &self,
+            parent_window: &str,
+            fd: OwnedFd,
+            options: HashMap<String, zbus::zvariant::OwnedValue>,
+        ) -> zbus::zvariant::OwnedObjectPath {
+            assert_eq!(parent_window, "");
+            let mut file = File::from(std::os::fd::OwnedFd::from(fd));
+            let mut contents = String::new();
+            file.rewind().unwrap();
+            file.read_to_string(&mut contents).unwrap();
+            let call = Call::File(contents, flag(&options, "ask"), flag(&options, "writable"));
+            self.0.lock().unwrap().push(call);
+            zbus::zvariant::ObjectPath::from_static_str_unchecked("/request/2").into()
+        }
+    }
+
+    #[test]
+    fn test_open_with_stub_portal() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some(bus) = PrivateBus::start(dir.path()) else {
+            return;
+        };
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let _service = bus
+            .builder()
+            .serve_at(PORTAL_PATH, StubPortal(Arc::clone(&calls)))
+            .unwrap()
+            .name(PORTAL_DESTINATION)
+            .unwrap()
+            .build()
+            .unwrap();
+        let client = bus.connect();
+
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "hello portal").unwrap();
+
+        open_on(
+            &client,
+            &"https://example.com/?q=1".parse().unwrap(),
+            PortalOptions::default(),
+        )
+        .unwrap();
+        open_on(
+            &client,
+            &PathOrURI::from(file.clone()),
+            PortalOptions {
+                ask: true,
+                writable: true,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                Call::Uri("https://example.com/?q=1".into(), false),
+                Call::File("hello portal".into(), true, true),
+            ]
+        );
+    }
+}
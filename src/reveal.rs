@@ -0,0 +1,231 @@
+//! Revealing files in the system file manager, with the files selected.
+
+use std::path::{Path, PathBuf};
+
+use path_clean::PathClean;
+
+use crate::{platform::PlatformOptions, CommandSpec, PathOrURI, Platform, Result};
+
+/// Makes `path` absolute if generating commands for the current platform. Paths for other
+/// platforms are used as-is, since they cannot be resolved here.
+fn absolute(platform: Platform, path: &Path) -> Result<PathBuf> {
+    if platform.is_current() {
+        Ok(std::env::current_dir()?.join(path).clean())
+    } else {
+        Ok(path.to_path_buf())
+    }
+}
+
+/// Generate the commands that reveal the paths without using D-Bus.
+pub(crate) fn specs(platform: Platform, paths: &[PathBuf]) -> Result<Vec<CommandSpec>> {
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let paths = paths
+        .iter()
+        .map(|path| {
+            PathOrURI::from(path.clone()).validate()?;
+            absolute(platform, path)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    match platform {
+        Platform::MacOS => {
+            crate::ensure_command_on(platform, "open")?;
+            let args = paths
+                .iter()
+                .map(|path| PathOrURI::from(path.clone()).to_arg())
+                .collect::<Result<Vec<_>>>()?;
+            Ok(vec![CommandSpec::new("open").arg("-R").args(args)])
+        }
+        Platform::Windows => {
+            crate::ensure_command_on(platform, "explorer")?;
+            Ok(paths
+                .iter()
+                .map(|path| CommandSpec::new("explorer").arg(format!("/select,{}", path.display())))
+                .collect())
+        }
+        Platform::Linux => {
+            let mut parents: Vec<&Path> = Vec::new();
+            for parent in paths.iter().map(|path| path.parent().unwrap_or(path)) {
+                if !parents.contains(&parent) {
+                    parents.push(parent);
+                }
+            }
+            parents
+                .into_iter()
+                .map(|parent| {
+                    platform.open(
+                        &PathOrURI::from(parent.to_path_buf()),
+                        PlatformOptions::default(),
+                    )
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(all(feature = "dbus", not(any(target_os = "windows", target_os = "macos"))))]
+mod dbus {
+    use std::path::PathBuf;
+
+    use zbus::blocking::{fdo::DBusProxy, Connection};
+
+    use crate::{PathOrURI, Result};
+
+    pub(super) const FILE_MANAGER_NAME: &str = "org.freedesktop.FileManager1";
+    pub(super) const FILE_MANAGER_PATH: &str = "/org/freedesktop/FileManager1";
+
+    /// Returns whether a file manager service is running or can be activated on the bus.
+    fn has_file_manager(connection: &Connection) -> Result<bool> {
+        let proxy = DBusProxy::new(connection)?;
+        let name = FILE_MANAGER_NAME.try_into().map_err(zbus::Error::from)?;
+        if proxy.name_has_owner(name).map_err(zbus::Error::from)? {
+            return Ok(true);
+        }
+        Ok(proxy
+            .list_activatable_names()
+            .map_err(zbus::Error::from)?
+            .iter()
+            .any(|name| name.as_str() == FILE_MANAGER_NAME))
+    }
+
+    /// Ask the file manager on `connection` to show the given absolute paths.
+    ///
+    /// Returns `false` if there is no file manager service.
+    pub(super) fn show_items(connection: &Connection, paths: &[PathBuf]) -> Result<bool> {
+        if !has_file_manager(connection)? {
+            return Ok(false);
+        }
+
+        let uris = paths
+            .iter()
+            .map(|path| Ok(PathOrURI::from(path.clone()).uri()?.to_string()))
+            .collect::<Result<Vec<_>>>()?;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!("revealing {:?} with {}", uris, FILE_MANAGER_NAME);
+
+        connection.call_method(
+            Some(FILE_MANAGER_NAME),
+            FILE_MANAGER_PATH,
+            Some(FILE_MANAGER_NAME),
+            "ShowItems",
+            &(uris, ""),
+        )?;
+        Ok(true)
+    }
+}
+
+/// Reveal the paths in the file manager of the current platform.
+pub(crate) fn reveal(paths: &[PathBuf]) -> Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    #[cfg(all(feature = "dbus", not(any(target_os = "windows", target_os = "macos"))))]
+    if let Ok(connection) = zbus::blocking::Connection::session() {
+        let paths = paths
+            .iter()
+            .map(|path| absolute(Platform::current(), path))
+            .collect::<Result<Vec<_>>>()?;
+        if dbus::show_items(&connection, &paths)? {
+            return Ok(());
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!("no file manager service, opening parent directories");
+    } else {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("could not connect to session bus, opening parent directories");
+    }
+
+    for spec in specs(Platform::current(), paths)? {
+        std::process::Command::from(spec).spawn()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths() -> Vec<PathBuf> {
+        vec![
+            PathBuf::from("/Users/me/a.txt"),
+            PathBuf::from("/Users/me/b c.txt"),
+        ]
+    }
+
+    #[test]
+    fn test_specs_macos() {
+        assert_eq!(
+            specs(Platform::MacOS, &paths()).unwrap(),
+            vec![CommandSpec::new("open").args(["-R", "/Users/me/a.txt", "/Users/me/b c.txt"])]
+        );
+    }
+
+    #[test]
+    fn test_specs_windows() {
+        assert_eq!(
+            specs(Platform::Windows, &paths()).unwrap(),
+            vec![
+                CommandSpec::new("explorer").arg("/select,/Users/me/a.txt"),
+                CommandSpec::new("explorer").arg("/select,/Users/me/b c.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_specs_empty() {
+        assert_eq!(specs(Platform::current(), &[]).unwrap(), vec![]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "dbus", not(any(target_os = "windows", target_os = "macos"))))]
+    fn test_show_items_with_stub_file_manager() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::test_util::PrivateBus;
+
+        struct StubFileManager(Arc<Mutex<Vec<Vec<String>>>>);
+
+        #[allow(clippy::needless_pass_by_value)]
+        #[zbus::interface(name = "org.freedesktop.FileManager1")]
+        impl StubFileManager {
+            fn show_items(&self, uris: Vec<String>, startup_id: &str) {
+                assert_eq!(startup_id, "");
+                self.0.lock().unwrap().push(uris);
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let Some(bus) = PrivateBus::start(dir.path()) else {
+            return;
+        };
+        let client = bus.connect();
+        let paths = paths();
+
+        assert!(!dbus::show_items(&client, &paths).unwrap());
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let _service = bus
+            .builder()
+            .serve_at(dbus::FILE_MANAGER_PATH, StubFileManager(Arc::clone(&calls)))
+            .unwrap()
+            .name(dbus::FILE_MANAGER_NAME)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(dbus::show_items(&client, &paths).unwrap());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![vec![
+                "file:///Users/me/a.txt".to_string(),
+                "file:///Users/me/b%20c.txt".to_string()
+            ]]
+        );
+    }
+}
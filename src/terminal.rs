@@ -0,0 +1,218 @@
+//! Running programs that need a terminal, such as `vim`, from applications without one.
+
+use std::path::PathBuf;
+
+use crate::{desktop_entry::DesktopEntry, CommandSpec, Environment, Error, Result, TERMINAL_ENV};
+
+/// Editors that only run in a terminal, matched by the file name of the program.
+const TERMINAL_EDITORS: &[&str] = &[
+    "ed", "ee", "elvis", "helix", "hx", "jed", "joe", "kak", "mcedit", "mg", "micro", "nano", "ne",
+    "neovim", "nvi", "nvim", "pico", "rnano", "vi", "vim", "vis",
+];
+
+/// Arguments that make `emacs` and `emacsclient` run in the terminal.
+const EMACS_TERMINAL_ARGS: &[&str] = &["-nw", "-t", "--no-window-system", "--tty"];
+
+/// Terminal emulators to try, in order, and the arguments that come before the command to run.
+///
+/// The arguments must make the emulator exit only once the command does, so that callers can wait
+/// for the editor. Several emulators otherwise hand the command to an instance that is already
+/// running and exit immediately.
+const EMULATORS: &[(&str, &[&str])] = &[
+    ("xdg-terminal-exec", &[]),
+    ("x-terminal-emulator", &["-e"]),
+    ("foot", &[]),
+    ("kitty", &[]),
+    ("alacritty", &["-e"]),
+    ("wezterm", &["start", "--always-new-process", "--"]),
+    ("gnome-terminal", &["--wait", "--"]),
+    ("konsole", &["--separate", "-e"]),
+    ("xfce4-terminal", &["--disable-server", "-x"]),
+    ("xterm", &["-e"]),
+];
+
+/// Returns whether the program of `spec` only runs in a terminal.
+///
+/// This is decided by a built-in list of editors and by `Terminal=true` in the desktop entry named
+/// after the program, if any, in `data_dirs`.
+pub(crate) fn needs_terminal(spec: &CommandSpec, data_dirs: &[PathBuf]) -> bool {
+    let name = crate::editor::program_name(&spec.program);
+    if TERMINAL_EDITORS.contains(&name.as_str()) {
+        return true;
+    }
+    if name == "emacs" || name == "emacsclient" {
+        return spec
+            .args
+            .iter()
+            .any(|arg| EMACS_TERMINAL_ARGS.contains(&arg.as_str()));
+    }
+
+    DesktopEntry::find(&name, data_dirs).is_some_and(|entry| {
+        entry.terminal
+            && entry
+                .program()
+                .is_some_and(|program| crate::editor::program_name(&program) == name)
+    })
+}
+
+/// Wraps `spec` in a command that runs it in a terminal emulator.
+///
+/// The emulator is taken from [`TERMINAL_ENV`] if set, or else is the first of
+/// `xdg-terminal-exec`, `x-terminal-emulator`, and a list of common emulators that exists on the
+/// `PATH` in `env`.
+///
+/// # Errors
+///
+/// - [`Error::ParseCommand`] if [`TERMINAL_ENV`] has malformed quoting.
+/// - [`Error::NoOpener`] if no terminal emulator is found.
+pub(crate) fn wrap<E: Environment + ?Sized>(spec: CommandSpec, env: &E) -> Result<CommandSpec> {
+    let (emulator, mut args) = emulator(env)?;
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!("running {} in terminal emulator {}", spec.program, emulator);
+
+    args.push(spec.program.clone());
+    args.extend(spec.args.iter().cloned());
+    Ok(CommandSpec {
+        program: emulator,
+        args,
+        ..spec
+    })
+}
+
+/// Returns the terminal emulator to use and the arguments that come before the command to run.
+fn emulator<E: Environment + ?Sized>(env: &E) -> Result<(String, Vec<String>)> {
+    let from_env = match env.var(TERMINAL_ENV) {
+        Some(value) => crate::split_command(TERMINAL_ENV, &value.to_string_lossy())?,
+        None => None,
+    };
+    if let Some((program, mut args)) = from_env {
+        let name = crate::editor::program_name(&program);
+        match EMULATORS.iter().find(|(emulator, _)| *emulator == name) {
+            Some((_, exec_args)) => args.extend(exec_args.iter().map(ToString::to_string)),
+            None => args.push("-e".to_string()),
+        }
+        return Ok((program, args));
+    }
+
+    EMULATORS
+        .iter()
+        .find(|(emulator, _)| env.which(emulator).is_ok())
+        .map(|(emulator, args)| {
+            (
+                (*emulator).to_string(),
+                args.iter().map(ToString::to_string).collect(),
+            )
+        })
+        .ok_or_else(|| Error::NoOpener {
+            tried: std::iter::once(format!("${TERMINAL_ENV}"))
+                .chain(
+                    EMULATORS
+                        .iter()
+                        .map(|(emulator, _)| (*emulator).to_string()),
+                )
+                .collect(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::env;
+
+    #[test]
+    fn test_needs_terminal() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path().join("applications");
+        std::fs::create_dir_all(&apps).unwrap();
+        std::fs::write(
+            apps.join("tui-edit.desktop"),
+            "[Desktop Entry]\nExec=tui-edit %F\nTerminal=true\n",
+        )
+        .unwrap();
+        std::fs::write(
+            apps.join("gui-edit.desktop"),
+            "[Desktop Entry]\nExec=gui-edit %F\nTerminal=false\n",
+        )
+        .unwrap();
+        let dirs = [dir.path().to_path_buf()];
+
+        for (spec, expected) in [
+            (CommandSpec::new("/usr/bin/vim"), true),
+            (CommandSpec::new("nano").arg("file"), true),
+            (CommandSpec::new("emacs").arg("-nw"), true),
+            (CommandSpec::new("emacs"), false),
+            (CommandSpec::new("code").arg("--wait"), false),
+            (CommandSpec::new("tui-edit"), true),
+            (CommandSpec::new("gui-edit"), false),
+        ] {
+            assert_eq!(needs_terminal(&spec, &dirs), expected, "{spec}");
+        }
+    }
+
+    #[test]
+    fn test_wrap_blocks() {
+        let spec = CommandSpec::new("vim").arg("notes.md");
+        for (emulator, expected) in [
+            ("xdg-terminal-exec", vec![]),
+            ("x-terminal-emulator", vec!["-e"]),
+            ("foot", vec![]),
+            ("kitty", vec![]),
+            ("alacritty", vec!["-e"]),
+            ("wezterm", vec!["start", "--always-new-process", "--"]),
+            ("gnome-terminal", vec!["--wait", "--"]),
+            ("konsole", vec!["--separate", "-e"]),
+            ("xfce4-terminal", vec!["--disable-server", "-x"]),
+            ("xterm", vec!["-e"]),
+        ] {
+            let mut args = expected;
+            args.extend(["vim", "notes.md"]);
+            assert_eq!(
+                wrap(spec.clone(), &env(&[("TERMINAL", emulator)])).unwrap(),
+                CommandSpec::new(emulator).args(args),
+                "{emulator}"
+            );
+        }
+        assert_eq!(EMULATORS.len(), 10);
+    }
+
+    #[test]
+    fn test_wrap_terminal_env() {
+        let spec = CommandSpec::new("vim").args(["+3", "notes.md"]);
+        assert_eq!(
+            wrap(spec.clone(), &env(&[("TERMINAL", "my-term --class edit")])).unwrap(),
+            CommandSpec::new("my-term").args(["--class", "edit", "-e", "vim", "+3", "notes.md"])
+        );
+        assert_eq!(
+            wrap(spec, &env(&[("TERMINAL", "gnome-terminal")])).unwrap(),
+            CommandSpec::new("gnome-terminal").args(["--wait", "--", "vim", "+3", "notes.md"])
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_wrap_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let spec = CommandSpec::new("nano").arg("notes.md").current_dir("/tmp");
+
+        assert!(matches!(
+            wrap(spec.clone(), &env(&[("PATH", path)])),
+            Err(Error::NoOpener { tried }) if tried[0] == "$TERMINAL" && tried.len() == EMULATORS.len() + 1
+        ));
+
+        for emulator in ["alacritty", "xterm"] {
+            let file = dir.path().join(emulator);
+            std::fs::write(&file, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        assert_eq!(
+            wrap(spec, &env(&[("PATH", path)])).unwrap(),
+            CommandSpec::new("alacritty")
+                .args(["-e", "nano", "notes.md"])
+                .current_dir("/tmp")
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Helpers shared by tests.
+
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::Environment;
+
+#[cfg(all(feature = "dbus", not(any(target_os = "windows", target_os = "macos"))))]
+pub(crate) use self::dbus::PrivateBus;
+
+/// Returns an [`Environment`] with only the given variables.
+pub(crate) fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+    vars.iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// An [`Environment`] of another system, with the given variables and programs.
+#[derive(Debug, Default)]
+pub(crate) struct FakeSystem {
+    vars: HashMap<String, String>,
+    programs: Vec<String>,
+    files: HashMap<PathBuf, String>,
+}
+
+impl FakeSystem {
+    /// Sets the variable `name` to `value`.
+    pub(crate) fn var(mut self, name: &str, value: &str) -> Self {
+        self.vars.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds a file at `path` with the given contents.
+    pub(crate) fn file(mut self, path: &str, contents: &str) -> Self {
+        self.files.insert(PathBuf::from(path), contents.to_string());
+        self
+    }
+
+    /// Adds `program`, which is found at `/usr/bin/<program>`.
+    pub(crate) fn program(mut self, program: &str) -> Self {
+        self.programs.push(program.to_string());
+        self
+    }
+}
+
+impl Environment for FakeSystem {
+    fn var(&self, name: &str) -> Option<OsString> {
+        self.vars.var(name)
+    }
+
+    fn read_file(&self, path: &Path) -> Option<String> {
+        self.files.get(path).cloned()
+    }
+
+    fn which(&self, program: &str) -> which::Result<PathBuf> {
+        if self.programs.iter().any(|other| other == program) {
+            Ok(PathBuf::from("/usr/bin").join(program))
+        } else {
+            Err(which::Error::CannotFindBinaryPath)
+        }
+    }
+}
+
+#[cfg(all(feature = "dbus", not(any(target_os = "windows", target_os = "macos"))))]
+mod dbus {
+    use std::{
+        io::{BufRead, BufReader},
+        path::Path,
+        process::{Child, Command, Stdio},
+    };
+
+    /// A `dbus-daemon` running on a socket in a temporary directory, killed when dropped.
+    pub(crate) struct PrivateBus {
+        child: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        /// Starts a private bus, returning `None` if `dbus-daemon` is not installed.
+        pub(crate) fn start(dir: &Path) -> Option<Self> {
+            let config = dir.join("bus.conf");
+            std::fs::write(
+                &config,
+                format!(
+                    r#"<busconfig>
+                        <type>session</type>
+                        <listen>unix:dir={}</listen>
+                        <policy context="default">
+                            <allow send_destination="*"/>
+                            <allow receive_sender="*"/>
+                            <allow own="*"/>
+                        </policy>
+                    </busconfig>"#,
+                    dir.display()
+                ),
+            )
+            .unwrap();
+
+            let Ok(mut child) = Command::new("dbus-daemon")
+                .arg(format!("--config-file={}", config.display()))
+                .args(["--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+            else {
+                eprintln!("dbus-daemon not found, skipping");
+                return None;
+            };
+
+            let mut address = String::new();
+            BufReader::new(child.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Some(Self {
+                child,
+                address: address.trim().to_string(),
+            })
+        }
+
+        /// Returns a builder for a new connection to this bus.
+        pub(crate) fn builder(&self) -> zbus::blocking::connection::Builder<'_> {
+            zbus::blocking::connection::Builder::address(self.address.as_str()).unwrap()
+        }
+
+        /// Opens a new connection to this bus.
+        pub(crate) fn connect(&self) -> zbus::blocking::Connection {
+            self.builder().build().unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -1,13 +1,280 @@
-use crate::PathOrURI;
-use std::process::Command;
+use crate::{platform::PlatformOptions, CommandSpec, Error, PathOrURI, Platform};
 
-pub(crate) fn open(target: &PathOrURI) -> crate::Result {
-    crate::ensure_command("cmd")?;
+/// How targets are opened on Windows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WindowsStrategy {
+    /// Run `cmd /c start "" <target>`. This supports every feature, but briefly shows a console
+    /// window when run from a GUI application.
+    #[default]
+    Start,
+    /// Run `explorer <target>`. This shows no console window, but cannot wait for the
+    /// application to exit.
+    Explorer,
+    /// Run `rundll32 url.dll,FileProtocolHandler <target>`. This shows no console window, but
+    /// cannot wait for the application to exit.
+    Rundll32,
+    /// Run `Start-Process` in a hidden PowerShell window. This supports every feature, but is
+    /// slower to start than the other strategies.
+    PowerShell,
+}
+
+/// The window title passed to `start`. Without it, `start` uses the first quoted argument as the
+/// title instead of the program or target.
+const EMPTY_TITLE: &str = r#""""#;
+
+/// Characters that `cmd` interprets outside of quotes.
+const METACHARACTERS: &[char] = &['^', '&', '|', '<', '>', '(', ')', '%', '!', '"'];
+
+/// Escapes `word` so that `cmd` passes it to `start` unchanged.
+///
+/// Words without whitespace have each metacharacter escaped with `^`. Words with whitespace are
+/// quoted instead, since `start` splits its arguments on whitespace. `cmd` still expands variables
+/// and ends the quotes inside quoted words, so the quotes are closed around each `%` and `"`,
+/// which are then escaped with `^`.
+fn escape(word: &str) -> String {
+    let quoted = word.contains(char::is_whitespace);
+    let mut escaped = String::with_capacity(word.len() + 2);
+    if quoted {
+        escaped.push('"');
+    }
+
+    for c in word.chars() {
+        match c {
+            '%' | '"' if quoted => {
+                escaped.push('"');
+                escaped.push('^');
+                escaped.push(c);
+                escaped.push('"');
+            }
+            c if !quoted && METACHARACTERS.contains(&c) => {
+                escaped.push('^');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    if quoted {
+        escaped.push('"');
+    }
+    escaped
+}
+
+/// Quotes `word` as a literal PowerShell string.
+fn powershell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "''"))
+}
+
+/// Generate a command that runs `Start-Process` in PowerShell with the given arguments.
+fn powershell_command(args: &str, options: PlatformOptions<'_>) -> crate::Result<CommandSpec> {
+    options.ensure_command(Platform::Windows, "powershell")?;
+
+    let wait = if options.wait { " -Wait" } else { "" };
+    Ok(CommandSpec::new("powershell").args([
+        "-NoProfile".to_string(),
+        "-NonInteractive".to_string(),
+        "-WindowStyle".to_string(),
+        "Hidden".to_string(),
+        "-Command".to_string(),
+        format!("Start-Process {args}{wait}"),
+    ]))
+}
+
+/// Returns an error if waiting was requested, since the strategy cannot wait.
+fn ensure_no_wait(options: PlatformOptions<'_>) -> crate::Result<()> {
+    if options.wait {
+        return Err(Error::WaitUnsupported(format!(
+            "the {:?} Windows strategy returns before the application exits",
+            options.windows
+        )));
+    }
+    Ok(())
+}
+
+/// Generate a command that runs `start` with the given words, escaped for `cmd`.
+fn start_command(words: &[&str], options: PlatformOptions<'_>) -> crate::Result<CommandSpec> {
+    options.ensure_command(Platform::Windows, "cmd")?;
+    Ok(start_spec("cmd", words, options.wait))
+}
+
+/// Generate a command that runs `start` with the given words, escaped for `cmd`, without checking
+/// that `cmd` exists. `cmd` is the name of the program, which is `cmd.exe` when run from WSL.
+pub(crate) fn start_spec(cmd: &str, words: &[&str], wait: bool) -> CommandSpec {
+    let mut spec = CommandSpec::new(cmd)
+        .args(["/c", "start"])
+        .verbatim_args(true);
+    if wait {
+        spec = spec.arg("/wait");
+    }
+    spec.arg(EMPTY_TITLE)
+        .args(words.iter().map(|word| escape(word)))
+}
 
+pub(crate) fn open(target: &PathOrURI, options: PlatformOptions<'_>) -> crate::Result<CommandSpec> {
     #[cfg(feature = "tracing")]
     tracing::debug!("opening {} with default Windows handler", target);
 
-    let mut cmd = Command::new("cmd");
-    cmd.args(&["/c", "start", target.uri()?.to_string().as_str()]);
-    Ok(cmd)
+    let uri = target.uri()?;
+    match options.windows {
+        WindowsStrategy::Start => start_command(&[uri.as_str()], options),
+        WindowsStrategy::Explorer => {
+            ensure_no_wait(options)?;
+            options.ensure_command(Platform::Windows, "explorer")?;
+            // Explorer splits unquoted arguments on commas.
+            Ok(CommandSpec::new("explorer")
+                .arg(format!("\"{uri}\""))
+                .verbatim_args(true))
+        }
+        WindowsStrategy::Rundll32 => {
+            ensure_no_wait(options)?;
+            options.ensure_command(Platform::Windows, "rundll32")?;
+            // rundll32 passes the rest of the command line to the handler as-is.
+            Ok(CommandSpec::new("rundll32")
+                .args(["url.dll,FileProtocolHandler", uri.as_str()])
+                .verbatim_args(true))
+        }
+        WindowsStrategy::PowerShell => powershell_command(&powershell_quote(uri.as_str()), options),
+    }
+}
+
+/// Generate a command that opens the target with the given application.
+///
+/// With [`WindowsStrategy::Explorer`] and [`WindowsStrategy::Rundll32`], which cannot choose the
+/// application, `app` is run directly and must be on the `PATH` or a full path.
+pub(crate) fn open_with(
+    app: &str,
+    target: &PathOrURI,
+    options: PlatformOptions<'_>,
+) -> crate::Result<CommandSpec> {
+    #[cfg(feature = "tracing")]
+    tracing::debug!("opening {} with {}", target, app);
+
+    let uri = target.uri()?;
+    match options.windows {
+        WindowsStrategy::Start => start_command(&[app, uri.as_str()], options),
+        WindowsStrategy::Explorer | WindowsStrategy::Rundll32 => {
+            options.ensure_command(Platform::Windows, app)?;
+            Ok(CommandSpec::new(app).arg(uri.as_str()))
+        }
+        WindowsStrategy::PowerShell => powershell_command(
+            &format!(
+                "-FilePath {} -ArgumentList {}",
+                powershell_quote(app),
+                powershell_quote(uri.as_str())
+            ),
+            options,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_url(url: &str) -> CommandSpec {
+        open(&url.parse().unwrap(), PlatformOptions::default()).unwrap()
+    }
+
+    fn options(windows: WindowsStrategy, wait: bool) -> PlatformOptions<'static> {
+        PlatformOptions {
+            wait,
+            windows,
+            ..PlatformOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("https://example.com/"), "https://example.com/");
+        assert_eq!(escape("a&b|c<d>e^f"), "a^&b^|c^<d^>e^^f");
+        assert_eq!(escape("(%PATH%)!"), "^(^%PATH^%^)^!");
+        assert_eq!(
+            escape(r"C:\Program Files (x86)\App\app.exe"),
+            r#""C:\Program Files (x86)\App\app.exe""#
+        );
+        assert_eq!(escape("a %b% c"), r#""a "^%"b"^%" c""#);
+    }
+
+    #[test]
+    fn test_open_query_string() {
+        assert_eq!(
+            open_url("https://x/?a=1&b=2").to_string(),
+            r#"cmd /c start "" https://x/?a=1^&b=2"#
+        );
+    }
+
+    #[test]
+    fn test_open_percent() {
+        assert_eq!(
+            open_url("https://x/a%20b?q=%PATH%").to_string(),
+            r#"cmd /c start "" https://x/a^%20b?q=^%PATH^%"#
+        );
+    }
+
+    #[test]
+    fn test_open_unicode() {
+        assert_eq!(
+            open_url("https://example.com/ünïcödé?q=日本").to_string(),
+            r#"cmd /c start "" https://example.com/%C3%BCn%C3%AFc%C3%B6d%C3%A9?q=%E6%97%A5%E6%9C%AC"#
+                .replace('%', "^%")
+        );
+    }
+
+    #[test]
+    fn test_open_with_spaces() {
+        let spec = open_with(
+            r"C:\Program Files\App & Co\app.exe",
+            &"https://x/?a=1&b=2".parse().unwrap(),
+            options(WindowsStrategy::Start, true),
+        )
+        .unwrap();
+        assert_eq!(
+            spec.to_string(),
+            r#"cmd /c start /wait "" "C:\Program Files\App & Co\app.exe" https://x/?a=1^&b=2"#
+        );
+    }
+
+    #[test]
+    fn test_strategies() {
+        let target: PathOrURI = "https://x/?a=1,2&b=c".parse().unwrap();
+        let open = |strategy| open(&target, options(strategy, false)).unwrap();
+
+        assert_eq!(
+            open(WindowsStrategy::Explorer).to_string(),
+            r#"explorer "https://x/?a=1,2&b=c""#
+        );
+        assert_eq!(
+            open(WindowsStrategy::Rundll32).to_string(),
+            "rundll32 url.dll,FileProtocolHandler https://x/?a=1,2&b=c"
+        );
+        assert_eq!(
+            open(WindowsStrategy::PowerShell).args.last().unwrap(),
+            "Start-Process 'https://x/?a=1,2&b=c'"
+        );
+    }
+
+    #[test]
+    fn test_powershell_open_with_wait() {
+        let spec = open_with(
+            r"C:\Bob's Apps\app.exe",
+            &"https://x/".parse().unwrap(),
+            options(WindowsStrategy::PowerShell, true),
+        )
+        .unwrap();
+        assert_eq!(
+            spec.args.last().unwrap(),
+            r"Start-Process -FilePath 'C:\Bob''s Apps\app.exe' -ArgumentList 'https://x/' -Wait"
+        );
+    }
+
+    #[test]
+    fn test_strategies_without_wait() {
+        let target: PathOrURI = "https://x/".parse().unwrap();
+        for strategy in [WindowsStrategy::Explorer, WindowsStrategy::Rundll32] {
+            assert!(matches!(
+                open(&target, options(strategy, true)),
+                Err(Error::WaitUnsupported(_))
+            ));
+        }
+    }
 }
@@ -0,0 +1,284 @@
+//! Opening targets through the Windows host when running inside the Windows Subsystem for Linux.
+
+use std::path::{Component, Path};
+
+use path_clean::PathClean;
+
+use crate::{platform::PlatformOptions, CommandSpec, Environment, Error, PathOrURI, Platform};
+
+const OSRELEASE_FILE: &str = "/proc/sys/kernel/osrelease";
+const WSL_CONF_FILE: &str = "/etc/wsl.conf";
+const DEFAULT_AUTOMOUNT_ROOT: &str = "/mnt/";
+
+/// Returns whether the given kernel release string belongs to a WSL kernel.
+fn is_wsl_release(release: &str) -> bool {
+    let release = release.to_lowercase();
+    release.contains("microsoft") || release.contains("wsl")
+}
+
+/// Returns whether this process is running inside WSL.
+pub(crate) fn is_wsl() -> bool {
+    std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || std::env::var_os("WSL_INTEROP").is_some()
+        || std::fs::read_to_string(OSRELEASE_FILE).is_ok_and(|release| is_wsl_release(&release))
+}
+
+/// Finds the `root` setting in the `[automount]` section of a `wsl.conf` file.
+fn automount_root(conf: &str) -> Option<String> {
+    let mut in_automount = false;
+    for line in conf.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_automount = line.eq_ignore_ascii_case("[automount]");
+        } else if in_automount {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() == "root" {
+                    let value = value.trim().trim_matches('"');
+                    return Some(format!("{}/", value.trim_end_matches('/')));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Converts an absolute Linux path to the path Windows uses to access it, the same way
+/// `wslpath -w` does.
+///
+/// Paths under `automount_root` that start with a drive letter become drive paths
+/// (`/mnt/c/Users` becomes `C:\Users`). All other paths are accessed through the distribution's
+/// network share (`/home/me` becomes `\\wsl$\<distro>\home\me`).
+///
+/// Returns `None` if the path is relative or not valid UTF-8, or if it needs the network share and
+/// the distribution is unknown.
+fn windows_path(path: &Path, distro: Option<&str>, automount_root: &str) -> Option<String> {
+    if !path.is_absolute() {
+        return None;
+    }
+
+    let path = path.to_path_buf().clean();
+    let components: Vec<&str> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_str()),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    let root: Vec<&str> = automount_root
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+    if let Some(rest) = components.strip_prefix(root.as_slice()) {
+        if let Some((drive, rest)) = rest.split_first() {
+            let mut chars = drive.chars();
+            if let (Some(letter), None) = (chars.next(), chars.next()) {
+                if letter.is_ascii_alphabetic() {
+                    return Some(format!(
+                        "{}:\\{}",
+                        letter.to_ascii_uppercase(),
+                        rest.join("\\")
+                    ));
+                }
+            }
+        }
+    }
+
+    Some(format!("\\\\wsl$\\{}\\{}", distro?, components.join("\\")))
+}
+
+/// Returns the argument to pass to a Windows program to open the target.
+///
+/// The distribution is read from `WSL_DISTRO_NAME`, which is not set when the environment was
+/// cleared, e.g. by `sudo`, so only paths on Windows drives can be converted then. Relative paths
+/// are resolved against the current directory, since [`Opener`](crate::Opener) has already
+/// resolved them against its own.
+fn windows_target(target: &PathOrURI, env: &dyn Environment) -> crate::Result<String> {
+    match target {
+        PathOrURI::URI(uri) => Ok(uri.to_string()),
+        PathOrURI::Path(path) => {
+            let absolute = std::env::current_dir()?.join(path);
+            let distro = env
+                .var("WSL_DISTRO_NAME")
+                .map(|distro| distro.to_string_lossy().into_owned())
+                .filter(|distro| !distro.is_empty());
+            let root = env
+                .read_file(Path::new(WSL_CONF_FILE))
+                .and_then(|conf| automount_root(&conf))
+                .unwrap_or_else(|| DEFAULT_AUTOMOUNT_ROOT.to_string());
+            windows_path(&absolute, distro.as_deref(), &root)
+                .ok_or_else(|| Error::WslPath(path.clone()))
+        }
+    }
+}
+
+/// Open the target with the Windows host's default handler.
+///
+/// `wslview` is preferred, as it handles path conversion itself. Otherwise, `cmd.exe` and then
+/// `explorer.exe` are used with the target converted to a Windows path. Only `cmd.exe` can wait
+/// for the application to exit. Programs are looked up in `env`.
+pub(crate) fn open(
+    target: &PathOrURI,
+    options: PlatformOptions<'_>,
+    env: &dyn Environment,
+) -> crate::Result<CommandSpec> {
+    if !options.wait && env.which("wslview").is_ok() {
+        return crate::open_with_command::<&str>(options, Platform::Linux, "wslview", &[], target);
+    }
+
+    let win_target = windows_target(target, env)?;
+    if env.which("cmd.exe").is_ok() {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            "opening {} with cmd.exe through the Windows host",
+            win_target
+        );
+
+        return Ok(crate::windows::start_spec(
+            "cmd.exe",
+            &[&win_target],
+            options.wait,
+        ));
+    }
+    if options.wait {
+        return Err(Error::WaitUnsupported(
+            "cmd.exe is needed to wait for Windows applications from WSL".to_string(),
+        ));
+    }
+
+    crate::ensure_command_in(env, "explorer.exe")?;
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        "opening {} with explorer.exe through the Windows host",
+        win_target
+    );
+
+    // Explorer splits unquoted arguments on commas.
+    Ok(CommandSpec::new("explorer.exe")
+        .arg(format!("\"{win_target}\""))
+        .verbatim_args(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::test_util::FakeSystem;
+
+    #[test]
+    fn test_is_wsl_release() {
+        assert!(is_wsl_release("5.15.90.1-microsoft-standard-WSL2\n"));
+        assert!(is_wsl_release("4.4.0-19041-Microsoft"));
+        assert!(!is_wsl_release("6.1.0-18-amd64"));
+    }
+
+    #[test]
+    fn test_automount_root() {
+        assert_eq!(automount_root(""), None);
+        assert_eq!(
+            automount_root("[boot]\nsystemd=true\n[automount]\nenabled = true\nroot = /\n"),
+            Some("/".into())
+        );
+        assert_eq!(
+            automount_root("[automount]\nroot = \"/windir\"\n"),
+            Some("/windir/".into())
+        );
+        assert_eq!(automount_root("[network]\nroot = /x/\n"), None);
+    }
+
+    #[test]
+    fn test_windows_path_drive() {
+        let convert = |p: &str| windows_path(Path::new(p), Some("Ubuntu"), DEFAULT_AUTOMOUNT_ROOT);
+        assert_eq!(convert("/mnt/c"), Some("C:\\".into()));
+        assert_eq!(
+            convert("/mnt/c/Users/me/My File.txt"),
+            Some("C:\\Users\\me\\My File.txt".into())
+        );
+        assert_eq!(convert("/mnt/d/./data/../docs/"), Some("D:\\docs".into()));
+    }
+
+    #[test]
+    fn test_windows_path_custom_root() {
+        assert_eq!(
+            windows_path(Path::new("/e/music"), Some("Debian"), "/"),
+            Some("E:\\music".into())
+        );
+        assert_eq!(
+            windows_path(Path::new("/mnt/c/x"), Some("Debian"), "/"),
+            Some("\\\\wsl$\\Debian\\mnt\\c\\x".into())
+        );
+    }
+
+    #[test]
+    fn test_cmd_escaping() {
+        let start =
+            |target: &str, wait| crate::windows::start_spec("cmd.exe", &[target], wait).to_string();
+        assert_eq!(
+            start("https://example.com/?a=1&calc", false),
+            r#"cmd.exe /c start "" https://example.com/?a=1^&calc"#
+        );
+        assert_eq!(
+            start(r"\\wsl$\Ubuntu\home\me\100%PATH%.txt", true),
+            r#"cmd.exe /c start /wait "" \\wsl$\Ubuntu\home\me\100^%PATH^%.txt"#
+        );
+        assert_eq!(
+            start(r"C:\Users\me\My ^Notes.txt", false),
+            r#"cmd.exe /c start "" "C:\Users\me\My ^Notes.txt""#
+        );
+        assert_eq!(
+            start("https://x/?q=a^b|c", false),
+            r#"cmd.exe /c start "" https://x/?q=a^^b^|c"#
+        );
+    }
+
+    #[test]
+    fn test_windows_target() {
+        let env = FakeSystem::default().file(WSL_CONF_FILE, "[automount]\nroot = /\n");
+        let target = |path: &str| windows_target(&PathOrURI::from(PathBuf::from(path)), &env);
+        assert_eq!(
+            target("/c/Users/me/a,b.txt").unwrap(),
+            r"C:\Users\me\a,b.txt"
+        );
+        // Without WSL_DISTRO_NAME, e.g. under sudo, the network share cannot be named.
+        assert!(matches!(
+            target("/home/me/notes.md"),
+            Err(Error::WslPath(_))
+        ));
+
+        let env = env.var("WSL_DISTRO_NAME", "Ubuntu");
+        assert_eq!(
+            windows_target(&PathOrURI::from(PathBuf::from("/home/me/notes.md")), &env).unwrap(),
+            r"\\wsl$\Ubuntu\home\me\notes.md"
+        );
+    }
+
+    #[test]
+    fn test_explorer_quoting() {
+        let env = FakeSystem::default()
+            .var("WSL_DISTRO_NAME", "Ubuntu")
+            .program("explorer.exe");
+        let target = PathOrURI::from(PathBuf::from("/mnt/c/a,b.txt"));
+        assert_eq!(
+            open(&target, PlatformOptions::default(), &env).unwrap(),
+            CommandSpec::new("explorer.exe")
+                .arg(r#""C:\a,b.txt""#)
+                .verbatim_args(true)
+        );
+    }
+
+    #[test]
+    fn test_windows_path_share() {
+        let convert = |p: &str| windows_path(Path::new(p), Some("Ubuntu"), DEFAULT_AUTOMOUNT_ROOT);
+        assert_eq!(
+            convert("/home/me/notes.md"),
+            Some("\\\\wsl$\\Ubuntu\\home\\me\\notes.md".into())
+        );
+        assert_eq!(
+            convert("/mnt/wsl/x"),
+            Some("\\\\wsl$\\Ubuntu\\mnt\\wsl\\x".into())
+        );
+        assert_eq!(convert("/mnt"), Some("\\\\wsl$\\Ubuntu\\mnt".into()));
+        assert_eq!(convert("relative/path"), None);
+    }
+}
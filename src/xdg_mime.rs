@@ -0,0 +1,248 @@
+//! Finding the default application for a target, following the [MIME applications
+//! specification].
+//!
+//! [MIME applications specification]: https://specifications.freedesktop.org/mime-apps-spec/latest/
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{desktop_entry::DesktopEntry, CommandSpec, PathOrURI};
+
+const DEFAULT_APPLICATIONS_GROUP: &str = "[Default Applications]";
+const MIME_CACHE_GROUP: &str = "[MIME Cache]";
+
+/// Returns the MIME type of the target.
+///
+/// URIs have the type `x-scheme-handler/<scheme>`. The type of a path is determined with
+/// `xdg-mime`, or `file` if that is not installed.
+pub(crate) fn mime_type(target: &PathOrURI) -> Option<String> {
+    if let PathOrURI::URI(uri) = target {
+        return Some(format!("x-scheme-handler/{}", uri.scheme()));
+    }
+
+    mime_queries(target).ok()?.into_iter().find_map(|spec| {
+        let output = Command::from(spec)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        let mime = String::from_utf8(output.stdout).ok()?;
+        let mime = mime.trim();
+        (output.status.success() && mime.contains('/')).then(|| mime.to_string())
+    })
+}
+
+/// Returns the commands that print the MIME type of a path, in order of preference.
+fn mime_queries(target: &PathOrURI) -> crate::Result<[CommandSpec; 2]> {
+    let arg = target.to_arg()?;
+    Ok([
+        CommandSpec::new("xdg-mime").args(["query", "filetype", &arg]),
+        CommandSpec::new("file").args(["--brief", "--mime-type", "--", &arg]),
+    ])
+}
+
+/// Returns the desktop file IDs listed for `mime` in the given group of a `mimeapps.list` or
+/// `mimeinfo.cache` file.
+fn listed_ids(contents: &str, group: &str, mime: &str) -> Vec<String> {
+    let mut in_group = false;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_group = line == group;
+        } else if in_group {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() == mime {
+                    return value
+                        .split(';')
+                        .map(str::trim)
+                        .filter(|id| !id.is_empty())
+                        .map(ToString::to_string)
+                        .collect();
+                }
+            }
+        }
+    }
+    Vec::new()
+}
+
+/// Returns the `mimeapps.list` files to check, in order of preference.
+fn mimeapps_files(
+    config_dirs: &[PathBuf],
+    data_dirs: &[PathBuf],
+    desktops: &[String],
+) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for dir in config_dirs {
+        files.extend(
+            desktops
+                .iter()
+                .map(|desktop| dir.join(format!("{desktop}-mimeapps.list"))),
+        );
+        files.push(dir.join("mimeapps.list"));
+    }
+    for dir in data_dirs.iter().map(|dir| dir.join("applications")) {
+        files.extend(
+            desktops
+                .iter()
+                .map(|desktop| dir.join(format!("{desktop}-mimeapps.list"))),
+        );
+        files.push(dir.join("mimeapps.list"));
+    }
+    files
+}
+
+fn first_entry(ids: Vec<String>, data_dirs: &[PathBuf]) -> Option<DesktopEntry> {
+    ids.into_iter()
+        .find_map(|id| DesktopEntry::find(&id, data_dirs))
+}
+
+/// Finds the default application for `mime`.
+///
+/// The `[Default Applications]` of each `mimeapps.list` are checked first, then the applications
+/// that declare support for the type in `mimeinfo.cache`.
+pub(crate) fn default_entry(
+    mime: &str,
+    config_dirs: &[PathBuf],
+    data_dirs: &[PathBuf],
+    desktops: &[String],
+) -> Option<DesktopEntry> {
+    let read = |path: &Path| std::fs::read_to_string(path).ok();
+
+    mimeapps_files(config_dirs, data_dirs, desktops)
+        .iter()
+        .filter_map(|file| read(file))
+        .find_map(|contents| {
+            first_entry(
+                listed_ids(&contents, DEFAULT_APPLICATIONS_GROUP, mime),
+                data_dirs,
+            )
+        })
+        .or_else(|| {
+            data_dirs
+                .iter()
+                .filter_map(|dir| read(&dir.join("applications/mimeinfo.cache")))
+                .find_map(|contents| {
+                    first_entry(listed_ids(&contents, MIME_CACHE_GROUP, mime), data_dirs)
+                })
+        })
+}
+
+/// Returns the XDG configuration directories, in order of preference.
+pub(crate) fn config_dirs() -> Vec<PathBuf> {
+    let home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    let dirs = std::env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+
+    home.into_iter()
+        .chain(dirs.split(':').filter(|s| !s.is_empty()).map(PathBuf::from))
+        .collect()
+}
+
+/// Returns the names of the current desktop environments, lowercased as used in file names.
+pub(crate) fn current_desktops() -> Vec<String> {
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|s| !s.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_mime_type_uri() {
+        assert_eq!(
+            mime_type(&"https://example.com".parse().unwrap()).as_deref(),
+            Some("x-scheme-handler/https")
+        );
+        assert_eq!(
+            mime_type(&"mailto:me@example.com".parse().unwrap()).as_deref(),
+            Some("x-scheme-handler/mailto")
+        );
+    }
+
+    #[test]
+    fn test_mime_queries_option_injection() {
+        let target = PathOrURI::from(PathBuf::from("-x.txt"));
+        assert_eq!(
+            mime_queries(&target).unwrap(),
+            [
+                CommandSpec::new("xdg-mime").args(["query", "filetype", "./-x.txt"]),
+                CommandSpec::new("file").args(["--brief", "--mime-type", "--", "./-x.txt"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_listed_ids() {
+        let contents = "[Added Associations]\ntext/plain=wrong.desktop;\n\
+                        [Default Applications]\ntext/plain = gedit.desktop;vim.desktop;\n";
+        assert_eq!(
+            listed_ids(contents, DEFAULT_APPLICATIONS_GROUP, "text/plain"),
+            vec!["gedit.desktop", "vim.desktop"]
+        );
+        assert!(listed_ids(contents, DEFAULT_APPLICATIONS_GROUP, "image/png").is_empty());
+    }
+
+    #[test]
+    fn test_default_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config");
+        let data = dir.path().join("data");
+        write(
+            &data.join("applications/gedit.desktop"),
+            "[Desktop Entry]\nName=Gedit\nExec=gedit %U\n",
+        );
+        write(
+            &data.join("applications/kate.desktop"),
+            "[Desktop Entry]\nName=Kate\nExec=kate %U\n",
+        );
+        write(
+            &data.join("applications/okular.desktop"),
+            "[Desktop Entry]\nName=Okular\nExec=okular %U\n",
+        );
+        write(
+            &config.join("mimeapps.list"),
+            "[Default Applications]\ntext/plain=missing.desktop;gedit.desktop;\n",
+        );
+        write(
+            &config.join("kde-mimeapps.list"),
+            "[Default Applications]\ntext/plain=kate.desktop\n",
+        );
+        write(
+            &data.join("applications/mimeinfo.cache"),
+            "[MIME Cache]\napplication/pdf=okular.desktop;\n",
+        );
+
+        let find = |mime: &str, desktops: &[String]| {
+            default_entry(
+                mime,
+                std::slice::from_ref(&config),
+                std::slice::from_ref(&data),
+                desktops,
+            )
+            .and_then(|entry| entry.name)
+        };
+        assert_eq!(find("text/plain", &[]).as_deref(), Some("Gedit"));
+        assert_eq!(
+            find("text/plain", &["kde".to_string()]).as_deref(),
+            Some("Kate")
+        );
+        assert_eq!(find("application/pdf", &[]).as_deref(), Some("Okular"));
+        assert_eq!(find("image/png", &[]), None);
+    }
+}